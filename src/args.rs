@@ -0,0 +1,1444 @@
+use crate::consistency::{ConsistencyLevel, SerialConsistencyLevel, StatementConsistency};
+use crate::distribution::{KeyDistribution, ValueSizeDistribution};
+use crate::populate::PopulateConfig;
+use crate::profile::{Profile, QuerySpec};
+use crate::schema::{Replication, SchemaConfig, TableOption};
+use crate::workload::{
+    BatchConfig, CollectionConfig, CollectionMix, CollectionType, CollectionWorkload,
+    CompactionWindow, CounterWorkload, KeyValueWorkload, LwtWorkload, ProfileWorkload,
+    ReplayWorkload, TimeSeriesConfig, TimeSeriesReadMix, TimeSeriesWorkload, WideConfig,
+    WideDeleteMix, WideReadMix, WideWorkload, Workload,
+};
+use crate::write_options::{TimestampMode, TtlDistribution, WriteOptions};
+use anyhow::{bail, Result};
+use clap::{Args as ClapArgs, Parser, Subcommand, ValueEnum};
+use parse_duration::parse;
+use scylla::batch::BatchType;
+use scylla::transport::session::{CurrentDeserializationApi, GenericSession, PoolSize};
+use scylla::SessionBuilder;
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "ScyllaDB performance tool",
+    long_about = "A tool for benchmarking Scylla DB performance"
+)]
+pub struct Args {
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[arg(
+        long,
+        global = true,
+        default_value = "1s",
+        value_parser = parse,
+        help = "Period of reporting results"
+    )]
+    pub report_period: Duration,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[command(about = "Create the schema and insert the initial dataset")]
+    Populate {
+        #[command(flatten)]
+        workload: WorkloadArgs,
+        #[command(flatten)]
+        populate: PopulateArgs,
+    },
+    #[command(about = "Benchmark against an existing dataset")]
+    Run(RunArgs),
+    #[command(
+        about = "Find the maximum rate the cluster sustains under a latency SLO, against an existing dataset"
+    )]
+    Search(SearchArgs),
+    #[command(about = "Read every key of the dataset back and check its value")]
+    Verify {
+        #[command(flatten)]
+        workload: WorkloadArgs,
+        #[arg(
+            short,
+            long,
+            default_value = "100",
+            help = "Number of concurrent read requests"
+        )]
+        concurrency: usize,
+    },
+    #[command(about = "Drop the schema, or remove all rows with --truncate")]
+    Cleanup {
+        #[command(flatten)]
+        workload: WorkloadArgs,
+        #[arg(long, help = "Truncate the tables instead of dropping the keyspace")]
+        truncate: bool,
+    },
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct ConnectionArgs {
+    #[arg(
+        short,
+        long,
+        global = true,
+        default_value = "127.0.0.1:9042",
+        help = "Comma-separated list of Scylla hosts with their ports. Example: '127.0.0.1:9042,127.0.0.2:9042,127.0.0.3:9042'"
+    )]
+    pub scylla_hosts: String,
+
+    #[arg(
+        short = 'u',
+        long,
+        global = true,
+        default_value = "cassandra",
+        help = "Scylla user"
+    )]
+    pub user: String,
+
+    #[arg(
+        long,
+        global = true,
+        default_value = "cassandra",
+        help = "Scylla password"
+    )]
+    pub password: String,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        default_value = "2",
+        help = "Number of connections per shard in the connection pool"
+    )]
+    pub pool_size: usize,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkloadKind {
+    /// Reads and writes of single-row partitions with a blob value
+    KeyValue,
+    /// Lightweight transactions, INSERT IF NOT EXISTS and compare-and-set UPDATE IF
+    Lwt,
+    /// Increments, decrements and reads of counter columns, checked against client-side totals
+    Counter,
+    /// Partitions with many clustering rows, single-row, slice, reverse and full-partition reads
+    Wide,
+    /// Appends, removes, overwrites and reads of a list, set, map or map of user-defined types
+    Collections,
+    /// Sensor points appended in time order to a TWCS table, latest-points and time-range reads
+    TimeSeries,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollectionKind {
+    List,
+    Set,
+    Map,
+    /// Map of a user-defined type
+    Udt,
+    /// Plain column of a user-defined type, updated field by field
+    UdtColumn,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BatchKind {
+    Logged,
+    Unlogged,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BatchGrouping {
+    /// Rows of any partitions, the coordinator forwards them to their replicas
+    Mixed,
+    /// Rows whose partition tokens are owned by the same replica shard. Not single-partition
+    /// batches, the key-value partitions only have one row
+    Token,
+}
+
+#[derive(ClapArgs, Debug, Clone)]
+pub struct WorkloadArgs {
+    #[arg(
+        short = 'w',
+        long,
+        value_enum,
+        default_value = "key-value",
+        help = "Built-in workload, ignored when --profile, --query or --replay is given"
+    )]
+    pub workload: WorkloadKind,
+
+    #[arg(
+        short,
+        long,
+        default_value = "10",
+        help = "Length of the key strings in the database"
+    )]
+    pub key_string_length: usize,
+
+    #[arg(
+        short,
+        long,
+        default_value = "10",
+        help = "Size of the value blobs in the database"
+    )]
+    pub value_blob_size: usize,
+
+    #[arg(
+        long,
+        help = "Distribution of value sizes in bytes: fixed:<size>, uniform:<min>,<max>, normal:<mean>,<stddev>[,<max>], lognormal:<median>,<sigma>[,<max>], histogram:<path>. Normal and log-normal sizes are capped at <max>, 16 MiB by default. The histogram file has '<size> <weight>' lines. Defaults to fixed:<value-blob-size>"
+    )]
+    pub value_size_distribution: Option<ValueSizeDistribution>,
+
+    #[arg(
+        short,
+        long,
+        default_value = "0.5",
+        help = "Percentage of reads in the workload. The rest will be writes. Must be between 0.0 and 1.0"
+    )]
+    pub reads_percentage: f32,
+
+    #[arg(
+        short,
+        long,
+        default_value = "1000",
+        help = "Total number of keys in the database"
+    )]
+    pub total_keys: usize,
+
+    #[arg(
+        long,
+        default_value = "uniform",
+        help = "Key access distribution: uniform, zipfian[:theta], gaussian[:stddev], latest[:theta], sequential, hotspot[:ops,keys]. Example: 'hotspot:0.9,0.1' sends 90% of operations to 10% of the keys"
+    )]
+    pub key_distribution: KeyDistribution,
+
+    #[arg(
+        long,
+        help = "Key access distribution of reads, defaults to --key-distribution"
+    )]
+    pub read_key_distribution: Option<KeyDistribution>,
+
+    #[arg(
+        long,
+        help = "Key access distribution of writes, defaults to --key-distribution"
+    )]
+    pub write_key_distribution: Option<KeyDistribution>,
+
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Seed from which keys and values are derived. Runs with the same seed and key options work with the same dataset"
+    )]
+    pub seed: u64,
+
+    #[arg(
+        long,
+        help = "Send the writes of the key-value and counter workloads in batches of this many rows, one by one if not set"
+    )]
+    pub batch_size: Option<usize>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "unlogged",
+        help = "Type of the write batches of the key-value workload, the counter workload always sends counter batches"
+    )]
+    pub batch_type: BatchKind,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "mixed",
+        help = "Rows of a key-value batch: 'mixed' takes rows of any partitions, 'token' only rows owned by the same replica shard. The key-value partitions have a single row, so 'token' batches span partitions of one replica instead of a single partition"
+    )]
+    pub batch_grouping: BatchGrouping,
+
+    #[arg(
+        long,
+        default_value = "100",
+        help = "Number of clustering rows in every partition of the wide workload, --total-keys is the number of partitions"
+    )]
+    pub rows_per_partition: usize,
+
+    #[arg(
+        long,
+        default_value = "10",
+        help = "LIMIT of the slice and reverse-order reads of the wide workload"
+    )]
+    pub slice_limit: usize,
+
+    #[arg(
+        long,
+        default_value = "100",
+        help = "Page size of the full-partition reads of the wide workload"
+    )]
+    pub page_size: usize,
+
+    #[arg(
+        long,
+        default_value = "1,1,1,1",
+        help = "Relative weights of the reads of the wide workload: <single>,<slice>,<reverse>,<full>. Example: '4,3,2,1'"
+    )]
+    pub wide_read_mix: WideReadMix,
+
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Percentage of deletes in the wide workload, taken out of the writes. Must be between 0.0 and 1.0 minus --reads-percentage, other workloads do not support deletes"
+    )]
+    pub deletes_percentage: f32,
+
+    #[arg(
+        long,
+        default_value = "1,1,1",
+        help = "Relative weights of the deletes of the wide workload: <row>,<range>,<cell>. Range deletes remove --slice-limit rows"
+    )]
+    pub wide_delete_mix: WideDeleteMix,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "list",
+        help = "Collection column of the collections workload"
+    )]
+    pub collection: CollectionKind,
+
+    #[arg(
+        long,
+        help = "Make the collection column frozen, frozen collections are only overwritten and read"
+    )]
+    pub frozen: bool,
+
+    #[arg(
+        long,
+        default_value = "10",
+        help = "Number of elements collections are overwritten with, --value-size-distribution gives the element sizes"
+    )]
+    pub collection_size: usize,
+
+    #[arg(
+        long,
+        default_value = "1,1,1,1",
+        help = "Relative weights of the operations of the collections workload, used instead of --reads-percentage: <append>,<remove>,<overwrite>,<read>"
+    )]
+    pub collection_mix: CollectionMix,
+
+    #[arg(
+        long,
+        value_parser = parse,
+        default_value = "1s",
+        help = "Time between two points of the history the time-series workload populates, --total-keys is the number of sensors"
+    )]
+    pub point_interval: Duration,
+
+    #[arg(
+        long,
+        value_parser = parse,
+        default_value = "1h",
+        help = "Time span of a partition of the time-series workload, partitions are keyed by sensor and time bucket"
+    )]
+    pub time_bucket: Duration,
+
+    #[arg(
+        long,
+        default_value = "1000",
+        help = "Points of every sensor inserted by the population of the time-series workload, ending at --history-end"
+    )]
+    pub history_points: usize,
+
+    #[arg(
+        long,
+        default_value = "1704067200000",
+        help = "End of the history of the time-series workload in milliseconds since the Unix epoch, the default is 2024-01-01 00:00 UTC. Like --seed it identifies the dataset, runs of a populated dataset need the same value"
+    )]
+    pub history_end: i64,
+
+    #[arg(
+        long,
+        default_value = "10",
+        help = "Number of points of the latest-points reads of the time-series workload"
+    )]
+    pub latest_points: usize,
+
+    #[arg(
+        long,
+        value_parser = parse,
+        default_value = "1m",
+        help = "Length of the time-range reads of the time-series workload"
+    )]
+    pub range_duration: Duration,
+
+    #[arg(
+        long,
+        default_value = "1,1",
+        help = "Relative weights of the reads of the time-series workload: <latest>,<range>. Example: '3,1'"
+    )]
+    pub time_series_read_mix: TimeSeriesReadMix,
+
+    #[arg(
+        long,
+        default_value = "1h",
+        help = "Window of the TimeWindowCompactionStrategy of the time-series table, in whole minutes, e.g. 30m, 1h, 1d"
+    )]
+    pub twcs_window: CompactionWindow,
+
+    #[arg(
+        long,
+        default_value = "1",
+        help = "Number of concurrent operations that fight over the same key in the lwt workload: that many consecutive operations go to the same key"
+    )]
+    pub lwt_contention: usize,
+
+    #[arg(
+        long,
+        default_value = "0.1",
+        help = "Percentage of INSERT IF NOT EXISTS operations in the lwt workload. The rest are compare-and-set updates. Must be between 0.0 and 1.0"
+    )]
+    pub lwt_inserts_percentage: f32,
+
+    #[arg(
+        long,
+        help = "TTL in seconds of the benchmark writes of the key-value, wide and time-series workloads and of replayed traces: fixed:<ttl>, uniform:<min>,<max>, normal:<mean>,<stddev>[,<max>], lognormal:<median>,<sigma>[,<max>], histogram:<path> with <ttl> <weight> lines. TTLs must be between 1 and 630720000 (20 years), normal and log-normal samples are clamped to 1 and their max, 630720000 if not given. The population is written without TTL. No TTL if not set"
+    )]
+    pub ttl: Option<TtlDistribution>,
+
+    #[arg(
+        long,
+        default_value = "server",
+        help = "Timestamps of the writes of the key-value, wide and time-series workloads and of replayed traces: 'server' leaves them to the coordinator, 'monotonic' sends strictly increasing client timestamps, 'skewed:<max skew>' moves client timestamps by a random offset of up to the max skew, e.g. 'skewed:500ms'"
+    )]
+    pub timestamps: TimestampMode,
+
+    #[arg(
+        long,
+        help = "Consistency level of reads, e.g. ONE, QUORUM, LOCAL_QUORUM. Defaults to the driver default, LOCAL_QUORUM"
+    )]
+    pub read_consistency: Option<ConsistencyLevel>,
+
+    #[arg(
+        long,
+        help = "Consistency level of writes, e.g. ONE, QUORUM, LOCAL_QUORUM. Defaults to the driver default, LOCAL_QUORUM"
+    )]
+    pub write_consistency: Option<ConsistencyLevel>,
+
+    #[arg(
+        long,
+        help = "Serial consistency level of conditional statements: SERIAL or LOCAL_SERIAL"
+    )]
+    pub serial_consistency: Option<SerialConsistencyLevel>,
+
+    #[arg(
+        long,
+        default_value = "test",
+        help = "Keyspace of the built-in workloads"
+    )]
+    pub keyspace: String,
+
+    #[arg(
+        long,
+        help = "Table of the built-in workloads, every workload has its own default name"
+    )]
+    pub table: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "1",
+        help = "Replication of the keyspace: a replication factor for SimpleStrategy, e.g. '3', or replication factors per datacenter for NetworkTopologyStrategy, e.g. 'dc1:3,dc2:3'"
+    )]
+    pub replication: Replication,
+
+    #[arg(
+        long,
+        help = "Create the keyspace with tablets enabled (true) or disabled (false), the cluster default if not set"
+    )]
+    pub tablets: Option<bool>,
+
+    #[arg(
+        long,
+        help = "Compaction of the table: '<class>[,<key>=<value>...]', e.g. 'LeveledCompactionStrategy,sstable_size_in_mb=160'. Replaces the TimeWindowCompactionStrategy of the time-series workload"
+    )]
+    pub compaction: Option<TableOption>,
+
+    #[arg(
+        long,
+        help = "Compression of the table: '<class>[,<key>=<value>...]', e.g. 'ZstdCompressor,compression_level=3', or 'none'"
+    )]
+    pub compression: Option<TableOption>,
+
+    #[arg(
+        long,
+        help = "Caching of the table: '<key>=<value>[,<key>=<value>...]', e.g. 'enabled=false'"
+    )]
+    pub caching: Option<TableOption>,
+
+    #[arg(
+        long,
+        help = "Path to a TOML or YAML workload profile with the schema, statements, bind-variable generators and phases. Overrides the key/value workload options, and the duration if the profile defines phases"
+    )]
+    pub profile: Option<PathBuf>,
+
+    #[arg(
+        long = "query",
+        value_name = "[WEIGHT:]CQL",
+        conflicts_with = "profile",
+        help = "CQL statement to benchmark against existing tables, can be repeated, e.g. \"4:SELECT * FROM ks.t WHERE id = ? AND ts > ?\". Statements are picked in proportion to their weights, 1 by default. Bind values are generated from the types of the bind markers, for row indexes picked from 0..--total-keys with --key-distribution"
+    )]
+    pub queries: Vec<QuerySpec>,
+
+    #[arg(
+        long,
+        value_name = "TRACE",
+        conflicts_with_all = ["profile", "queries"],
+        help = "Replay the operations of a trace file against the key/value table, at their recorded offsets. Every line holds '<offset in seconds>,<read|write|delete>,<key>,<value size>'. The benchmark ends after the last operation, populating inserts every key of the trace"
+    )]
+    pub replay: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value = "1.0",
+        requires = "replay",
+        help = "Speed factor of the replay, 2.0 issues the trace twice as fast as recorded"
+    )]
+    pub replay_speed: f64,
+}
+
+#[derive(ClapArgs, Debug, Clone)]
+pub struct PopulateArgs {
+    #[arg(
+        long,
+        default_value = "100",
+        help = "Number of concurrent insert requests while populating the initial dataset"
+    )]
+    pub populate_concurrency: usize,
+
+    #[arg(
+        long,
+        help = "Maximum inserts per second while populating the initial dataset, unlimited if not set"
+    )]
+    pub populate_rate: Option<f64>,
+
+    #[arg(
+        long,
+        default_value = "scylla-perf-populate.checkpoint",
+        help = "File where population progress is checkpointed, used by --resume"
+    )]
+    pub checkpoint: PathBuf,
+
+    #[arg(
+        long,
+        help = "Continue an interrupted population from the checkpoint instead of inserting every key again"
+    )]
+    pub resume: bool,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub workload: WorkloadArgs,
+
+    #[arg(
+        short,
+        long,
+        default_value = "1000",
+        help = "Number of concurrent requests at any given moment of time per executor"
+    )]
+    pub concurrency: usize,
+
+    #[arg(
+        long,
+        help = "Target rate in operations per second per executor. Requests are sent on a fixed timetable and latency is measured from the scheduled start, correcting for coordinated omission. Reports show the uncorrected and corrected latency percentiles in both report modes. If not set, requests are sent as soon as the previous ones finish"
+    )]
+    pub rate: Option<f64>,
+
+    #[arg(
+        short,
+        long,
+        default_value = "1",
+        help = "Number of executors to run in parallel. The schema and the dataset are shared, every executor works on its own slice of the keys"
+    )]
+    pub executors_count: usize,
+
+    #[arg(short, long, value_parser = parse, default_value = "10s", help = "Duration of the benchmark"
+    )]
+    pub duration: Duration,
+
+    #[arg(
+        long,
+        value_parser = parse,
+        default_value = "0s",
+        help = "Warm-up period at the start of the benchmark. The workload runs as usual, but its results are left out of the statistics and shown separately in the final report"
+    )]
+    pub warmup: Duration,
+
+    #[arg(
+        long,
+        value_parser = parse,
+        default_value = "10s",
+        help = "How long to wait for the in-flight requests once the benchmark stops, at the end of the duration or on SIGINT/SIGTERM. Requests still running after it are not waited for, and a run whose counters would be verified fails unverified"
+    )]
+    pub drain_timeout: Duration,
+
+    #[arg(
+        short = 'm',
+        long,
+        help = "Available modes: simple, percentile. simple uses less cpu and memory, but provides less information. percentile uses more cpu and memory, but provides more information i.e. 50th, 90th, 99th percentiles",
+        default_value = "simple"
+    )]
+    pub report_mode: String,
+
+    #[arg(
+        long,
+        help = "Populate the dataset before the benchmark, same as running 'populate' first"
+    )]
+    pub populate: bool,
+
+    #[command(flatten)]
+    pub populate_args: PopulateArgs,
+
+    #[arg(
+        long,
+        help = "Drop the keyspace after the benchmark, only the workload table if --table is given"
+    )]
+    pub cleanup: bool,
+
+    #[arg(
+        long,
+        help = "Compare the value of every read with the expected one and count missing rows and mismatched values. Only supported by the key/value workload"
+    )]
+    pub verify: bool,
+
+    #[arg(
+        long,
+        requires = "verify",
+        help = "Stop the benchmark with an error on the first missing row or mismatched value"
+    )]
+    pub stop_on_corruption: bool,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct SearchArgs {
+    #[command(flatten)]
+    pub workload: WorkloadArgs,
+
+    #[arg(
+        short,
+        long,
+        default_value = "1000",
+        help = "Maximum number of concurrent requests at any given moment of time"
+    )]
+    pub concurrency: usize,
+
+    #[arg(
+        long,
+        value_parser = parse,
+        default_value = "10ms",
+        help = "Latency SLO, a step passes if its latency at --slo-percentile is at most this"
+    )]
+    pub slo_latency: Duration,
+
+    #[arg(
+        long,
+        default_value = "99",
+        help = "Percentile the latency SLO applies to"
+    )]
+    pub slo_percentile: f64,
+
+    #[arg(
+        long,
+        default_value = "1000",
+        help = "Rate of the first step in operations per second"
+    )]
+    pub min_rate: f64,
+
+    #[arg(
+        long,
+        default_value = "1000000",
+        help = "Highest rate tried in operations per second"
+    )]
+    pub max_rate: f64,
+
+    #[arg(
+        long,
+        value_parser = parse,
+        default_value = "10s",
+        help = "Measured duration of each step"
+    )]
+    pub step_duration: Duration,
+
+    #[arg(
+        long,
+        value_parser = parse,
+        default_value = "2s",
+        help = "Warm-up before each step, its results are not judged against the SLO"
+    )]
+    pub step_warmup: Duration,
+
+    #[arg(
+        long,
+        default_value = "0.05",
+        help = "The search stops once the failed rate is within this fraction above the passed one"
+    )]
+    pub precision: f64,
+}
+
+impl SearchArgs {
+    /// Reject the search options that would panic the executor or never end the search
+    pub fn validate(&self) -> Result<()> {
+        if !(self.min_rate > 0.0 && self.min_rate <= self.max_rate && self.max_rate.is_finite()) {
+            bail!("--min-rate must be greater than 0 and at most --max-rate");
+        }
+        if !(self.precision > 0.0 && self.precision.is_finite()) {
+            bail!("--precision must be greater than 0");
+        }
+        if !(self.slo_percentile > 0.0 && self.slo_percentile <= 100.0) {
+            bail!("--slo-percentile must be greater than 0 and at most 100");
+        }
+        Ok(())
+    }
+}
+
+impl Command {
+    pub fn workload(&self) -> &WorkloadArgs {
+        match self {
+            Command::Populate { workload, .. }
+            | Command::Verify { workload, .. }
+            | Command::Cleanup { workload, .. } => workload,
+            Command::Run(args) => &args.workload,
+            Command::Search(args) => &args.workload,
+        }
+    }
+}
+
+impl ConnectionArgs {
+    pub async fn session(
+        &self,
+        keyspaces: Vec<String>,
+    ) -> Result<GenericSession<CurrentDeserializationApi>> {
+        let mut builder = SessionBuilder::new()
+            .user(&self.user, &self.password)
+            .pool_size(PoolSize::PerShard(
+                NonZeroUsize::new(self.pool_size).unwrap(),
+            ))
+            .keyspaces_to_fetch(keyspaces);
+        for host in self.scylla_hosts.split(',') {
+            builder = builder.known_node(host);
+        }
+        Ok(builder.build().await?)
+    }
+}
+
+impl fmt::Display for ConnectionArgs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pass_first4 = self.password.chars().take(4).collect::<String>();
+        let pass_after4 = self
+            .password
+            .chars()
+            .skip(4)
+            .map(|_| '*')
+            .collect::<String>();
+        write!(
+            f,
+            "scylla_host: {}, pool_size: {}, user: {}, password: {}",
+            self.scylla_hosts,
+            self.pool_size,
+            self.user,
+            pass_first4 + &pass_after4
+        )
+    }
+}
+
+impl WorkloadArgs {
+    pub fn value_sizes(&self) -> ValueSizeDistribution {
+        self.value_size_distribution
+            .clone()
+            .unwrap_or(ValueSizeDistribution::Fixed(self.value_blob_size))
+    }
+
+    pub fn load_profile(&self) -> Result<Option<Profile>> {
+        if !self.queries.is_empty() {
+            return Profile::from_queries(&self.queries).map(Some);
+        }
+        self.profile.as_deref().map(Profile::load).transpose()
+    }
+
+    /// Reject the options the built-in workloads cannot run with, so that the user gets an
+    /// error instead of a panic of the workload constructor
+    fn validate(&self) -> Result<()> {
+        // the names go into the CQL of the schema and the statements unquoted
+        let valid_name = |name: &str| {
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+        if !valid_name(&self.keyspace) {
+            bail!("--keyspace must only contain letters, digits and underscores");
+        }
+        if !self.table.as_deref().is_none_or(valid_name) {
+            bail!("--table must only contain letters, digits and underscores");
+        }
+        let built_in = self.profile.is_none() && self.queries.is_empty() && self.replay.is_none();
+        if self.deletes_percentage != 0.0
+            && !(built_in && matches!(self.workload, WorkloadKind::Wide))
+        {
+            bail!("--deletes-percentage is only supported by the wide workload");
+        }
+        if !built_in {
+            return Ok(());
+        }
+        if !(0.0..=1.0).contains(&self.reads_percentage) {
+            bail!("--reads-percentage must be between 0.0 and 1.0");
+        }
+        match self.workload {
+            WorkloadKind::Lwt => {
+                if !(0.0..=1.0).contains(&self.lwt_inserts_percentage) {
+                    bail!("--lwt-inserts-percentage must be between 0.0 and 1.0");
+                }
+                if self.lwt_contention == 0 {
+                    bail!("--lwt-contention must be at least 1");
+                }
+            }
+            WorkloadKind::KeyValue | WorkloadKind::Counter => {
+                if self.batch_size == Some(0) {
+                    bail!("--batch-size must be at least 1");
+                }
+                if self.workload == WorkloadKind::Counter
+                    && self.batch_size.is_some()
+                    && self.batch_grouping == BatchGrouping::Token
+                {
+                    bail!("--batch-grouping token is only supported by the key-value workload");
+                }
+            }
+            WorkloadKind::Wide => {
+                if self.deletes_percentage < 0.0
+                    || self.reads_percentage + self.deletes_percentage > 1.0
+                {
+                    bail!(
+                        "--deletes-percentage must be between 0.0 and 1.0 minus --reads-percentage"
+                    );
+                }
+                if self.rows_per_partition == 0 {
+                    bail!("--rows-per-partition must be at least 1");
+                }
+                if self.slice_limit == 0 || self.page_size == 0 {
+                    bail!("--slice-limit and --page-size must be at least 1");
+                }
+            }
+            WorkloadKind::Collections => {
+                if self.collection_size == 0 {
+                    bail!("--collection-size must be at least 1");
+                }
+                let mix = &self.collection_mix;
+                if self.frozen && mix.overwrite == 0 && mix.read == 0 {
+                    bail!("Frozen collections are only overwritten and read, --collection-mix needs an overwrite or read weight above 0 with --frozen");
+                }
+            }
+            WorkloadKind::TimeSeries => {
+                if self.point_interval.as_millis() == 0 || self.time_bucket.as_millis() == 0 {
+                    bail!("--point-interval and --time-bucket must be at least 1ms");
+                }
+                if self.latest_points == 0 {
+                    bail!("--latest-points must be at least 1");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the workload, from the profile if one is given or the key/value options otherwise
+    pub fn build(&self, profile: Option<Profile>) -> Result<Box<dyn Workload>> {
+        self.validate()?;
+        let workload: Box<dyn Workload> = match profile {
+            Some(profile) => Box::new(
+                ProfileWorkload::new(profile, self.key_distribution.clone(), self.seed)
+                    .with_default_rows(self.total_keys)
+                    .with_consistency(self.read_consistency(), self.write_consistency()),
+            ),
+            None if self.replay.is_some() => Box::new(self.replay()),
+            None => match self.workload {
+                WorkloadKind::KeyValue => Box::new(self.key_value()),
+                WorkloadKind::Lwt => Box::new(
+                    LwtWorkload::new(
+                        self.total_keys,
+                        self.key_string_length,
+                        self.lwt_contention,
+                        self.lwt_inserts_percentage,
+                        self.seed,
+                    )
+                    .with_consistency(self.write_consistency())
+                    .with_schema(self.schema()),
+                ),
+                WorkloadKind::Counter => {
+                    let workload = CounterWorkload::new(
+                        self.total_keys,
+                        self.key_string_length,
+                        self.reads_percentage,
+                        self.read_key_distribution
+                            .as_ref()
+                            .unwrap_or(&self.key_distribution),
+                        self.write_key_distribution
+                            .as_ref()
+                            .unwrap_or(&self.key_distribution),
+                        self.seed,
+                    )
+                    .with_consistency(self.read_consistency(), self.write_consistency())
+                    .with_schema(self.schema());
+                    match self.batch_size {
+                        Some(size) => Box::new(workload.with_batches(size)),
+                        None => Box::new(workload),
+                    }
+                }
+                WorkloadKind::Wide => Box::new(
+                    WideWorkload::new(
+                        self.total_keys,
+                        self.key_string_length,
+                        &self.value_sizes(),
+                        self.reads_percentage,
+                        self.read_key_distribution
+                            .as_ref()
+                            .unwrap_or(&self.key_distribution),
+                        self.write_key_distribution
+                            .as_ref()
+                            .unwrap_or(&self.key_distribution),
+                        self.seed,
+                        WideConfig {
+                            rows_per_partition: self.rows_per_partition,
+                            slice_limit: self.slice_limit,
+                            page_size: self.page_size,
+                            read_mix: self.wide_read_mix.clone(),
+                            deletes_percentage: self.deletes_percentage,
+                            delete_mix: self.wide_delete_mix.clone(),
+                        },
+                    )?
+                    .with_consistency(self.read_consistency(), self.write_consistency())
+                    .with_write_options(self.write_options())
+                    .with_schema(self.schema()),
+                ),
+                WorkloadKind::Collections => Box::new(
+                    CollectionWorkload::new(
+                        self.total_keys,
+                        self.key_string_length,
+                        &self.value_sizes(),
+                        self.read_key_distribution
+                            .as_ref()
+                            .unwrap_or(&self.key_distribution),
+                        self.write_key_distribution
+                            .as_ref()
+                            .unwrap_or(&self.key_distribution),
+                        self.seed,
+                        CollectionConfig {
+                            collection_type: match self.collection {
+                                CollectionKind::List => CollectionType::List,
+                                CollectionKind::Set => CollectionType::Set,
+                                CollectionKind::Map => CollectionType::Map,
+                                CollectionKind::Udt => CollectionType::Udt,
+                                CollectionKind::UdtColumn => CollectionType::UdtColumn,
+                            },
+                            frozen: self.frozen,
+                            size: self.collection_size,
+                            mix: self.collection_mix.clone(),
+                        },
+                    )?
+                    .with_consistency(self.read_consistency(), self.write_consistency())
+                    .with_schema(self.schema()),
+                ),
+                WorkloadKind::TimeSeries => Box::new(
+                    TimeSeriesWorkload::new(
+                        self.total_keys,
+                        self.key_string_length,
+                        &self.value_sizes(),
+                        self.reads_percentage,
+                        self.read_key_distribution
+                            .as_ref()
+                            .unwrap_or(&self.key_distribution),
+                        self.write_key_distribution
+                            .as_ref()
+                            .unwrap_or(&self.key_distribution),
+                        self.seed,
+                        TimeSeriesConfig {
+                            interval: self.point_interval,
+                            bucket: self.time_bucket,
+                            history: self.history_points,
+                            history_end: self.history_end,
+                            latest_points: self.latest_points,
+                            range: self.range_duration,
+                            read_mix: self.time_series_read_mix.clone(),
+                            window: self.twcs_window,
+                        },
+                    )?
+                    .with_consistency(self.read_consistency(), self.write_consistency())
+                    .with_write_options(self.write_options())
+                    .with_schema(self.schema()),
+                ),
+            },
+        };
+        Ok(workload)
+    }
+
+    pub fn key_value(&self) -> KeyValueWorkload {
+        let workload = KeyValueWorkload::new(
+            self.total_keys,
+            self.key_string_length,
+            &self.value_sizes(),
+            self.reads_percentage,
+            self.read_key_distribution
+                .as_ref()
+                .unwrap_or(&self.key_distribution),
+            self.write_key_distribution
+                .as_ref()
+                .unwrap_or(&self.key_distribution),
+            self.seed,
+        )
+        .with_consistency(self.read_consistency(), self.write_consistency())
+        .with_write_options(self.write_options())
+        .with_schema(self.schema());
+        match self.batch_size {
+            Some(size) => workload.with_batches(BatchConfig {
+                size,
+                batch_type: match self.batch_type {
+                    BatchKind::Logged => BatchType::Logged,
+                    BatchKind::Unlogged => BatchType::Unlogged,
+                },
+                group_by_token: self.batch_grouping == BatchGrouping::Token,
+            }),
+            None => workload,
+        }
+    }
+
+    fn replay(&self) -> ReplayWorkload {
+        ReplayWorkload::new(self.replay.clone().unwrap_or_default(), self.replay_speed)
+            .with_consistency(self.read_consistency(), self.write_consistency())
+            .with_write_options(self.write_options())
+            .with_schema(self.schema())
+    }
+
+    fn schema(&self) -> SchemaConfig {
+        SchemaConfig {
+            keyspace: self.keyspace.clone(),
+            table: self.table.clone(),
+            replication: self.replication.clone(),
+            tablets: self.tablets,
+            compaction: self.compaction.clone(),
+            compression: self.compression.clone(),
+            caching: self.caching.clone(),
+        }
+    }
+
+    /// Identity of the dataset the options describe, stored in the population checkpoint so
+    /// that `--resume` never continues the population of another dataset. It holds every
+    /// option that changes the rows the population writes
+    pub fn dataset(&self) -> String {
+        let workload = if let Some(profile) = &self.profile {
+            format!("profile {}", profile.display())
+        } else if let Some(trace) = &self.replay {
+            format!("replay {}", trace.display())
+        } else if !self.queries.is_empty() {
+            "queries".to_string()
+        } else {
+            self.workload
+                .to_possible_value()
+                .map_or_else(String::new, |v| v.get_name().to_string())
+        };
+        let mut dataset = format!(
+            "workload: {}, keyspace: {}, table: {}, seed: {}, total_keys: {}, key_string_length: {}",
+            workload,
+            self.keyspace,
+            self.table.as_deref().unwrap_or("default"),
+            self.seed,
+            self.total_keys,
+            self.key_string_length
+        );
+        if self.profile.is_some() || self.replay.is_some() || !self.queries.is_empty() {
+            return dataset;
+        }
+        let ttl = self
+            .ttl
+            .as_ref()
+            .map_or("none".to_string(), |t| t.to_string());
+        match self.workload {
+            WorkloadKind::KeyValue => {
+                dataset += &format!(", value_size: {}, ttl: {}", self.value_sizes(), ttl)
+            }
+            WorkloadKind::Lwt | WorkloadKind::Counter => {}
+            WorkloadKind::Wide => {
+                dataset += &format!(
+                    ", value_size: {}, rows_per_partition: {}, ttl: {}",
+                    self.value_sizes(),
+                    self.rows_per_partition,
+                    ttl
+                )
+            }
+            WorkloadKind::Collections => {
+                dataset += &format!(
+                    ", element_size: {}, collection: {:?}, frozen: {}, collection_size: {}",
+                    self.value_sizes(),
+                    self.collection,
+                    self.frozen,
+                    self.collection_size
+                )
+            }
+            WorkloadKind::TimeSeries => {
+                dataset += &format!(
+                    ", value_size: {}, point_interval: {:?}, time_bucket: {:?}, history_points: {}, history_end: {}, ttl: {}",
+                    self.value_sizes(),
+                    self.point_interval,
+                    self.time_bucket,
+                    self.history_points,
+                    self.history_end,
+                    ttl
+                )
+            }
+        }
+        dataset
+    }
+
+    /// Keyspaces the driver fetches the metadata of, for token-aware routing. Profiles and
+    /// custom statements can use any keyspace, so all of them are fetched
+    pub fn keyspaces_to_fetch(&self) -> Vec<String> {
+        if self.profile.is_some() || !self.queries.is_empty() {
+            Vec::new()
+        } else {
+            vec![self.keyspace.clone()]
+        }
+    }
+
+    fn write_options(&self) -> WriteOptions {
+        WriteOptions::new(self.ttl.clone(), self.timestamps)
+    }
+
+    fn read_consistency(&self) -> StatementConsistency {
+        StatementConsistency {
+            consistency: self.read_consistency,
+            serial_consistency: self.serial_consistency,
+        }
+    }
+
+    fn write_consistency(&self) -> StatementConsistency {
+        StatementConsistency {
+            consistency: self.write_consistency,
+            serial_consistency: self.serial_consistency,
+        }
+    }
+}
+
+impl fmt::Display for WorkloadArgs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_workload(f)?;
+        if self.profile.is_none() && self.queries.is_empty() {
+            write!(f, ",\n{}", self.schema())?;
+        }
+        Ok(())
+    }
+}
+
+impl WorkloadArgs {
+    fn fmt_workload(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(profile) = &self.profile {
+            return write!(
+                f,
+                "profile: {}, seed: {}, read_consistency: {}, write_consistency: {}",
+                profile.display(),
+                self.seed,
+                self.read_consistency(),
+                self.write_consistency()
+            );
+        }
+        if let Some(trace) = &self.replay {
+            return write!(
+                f,
+                "replay: {}, speed: {}, read_consistency: {}, write_consistency: {}, {}",
+                trace.display(),
+                self.replay_speed,
+                self.read_consistency(),
+                self.write_consistency(),
+                self.write_options()
+            );
+        }
+        if !self.queries.is_empty() {
+            let queries: Vec<String> = self.queries.iter().map(|q| q.to_string()).collect();
+            return write!(
+                f,
+                "queries: [{}],\n\
+                 total_keys: {}, key_distribution: {}, seed: {}, read_consistency: {}, write_consistency: {}",
+                queries.join(", "),
+                self.total_keys,
+                self.key_distribution,
+                self.seed,
+                self.read_consistency(),
+                self.write_consistency()
+            );
+        }
+        if self.workload == WorkloadKind::Lwt {
+            return write!(
+                f,
+                "workload: lwt, key_string_length: {}, total_keys: {}, contention: {}, inserts_percentage: {}, seed: {},\n\
+                 write_consistency: {}",
+                self.key_string_length,
+                self.total_keys,
+                self.lwt_contention,
+                self.lwt_inserts_percentage,
+                self.seed,
+                self.write_consistency()
+            );
+        }
+        if self.workload == WorkloadKind::Counter {
+            return write!(
+                f,
+                "workload: counter, key_string_length: {}, reads_percentage: {}, total_keys: {}, seed: {},\n\
+                 read_consistency: {}, write_consistency: {}, batches: {}",
+                self.key_string_length,
+                self.reads_percentage,
+                self.total_keys,
+                self.seed,
+                self.read_consistency(),
+                self.write_consistency(),
+                match self.batch_size {
+                    Some(size) => format!("Counter of {} rows", size),
+                    None => "none".to_string(),
+                }
+            );
+        }
+        if self.workload == WorkloadKind::Collections {
+            return write!(
+                f,
+                "workload: collections, collection: {:?}, frozen: {}, collection_size: {}, element_size: {}, mix: {},\n\
+                 key_string_length: {}, total_keys: {}, seed: {}, read_consistency: {}, write_consistency: {}",
+                self.collection,
+                self.frozen,
+                self.collection_size,
+                self.value_sizes(),
+                self.collection_mix,
+                self.key_string_length,
+                self.total_keys,
+                self.seed,
+                self.read_consistency(),
+                self.write_consistency()
+            );
+        }
+        if self.workload == WorkloadKind::TimeSeries {
+            return write!(
+                f,
+                "workload: time-series, key_string_length: {}, value_size: {}, reads_percentage: {}, sensors: {}, point_interval: {:?},\n\
+                 time_bucket: {:?}, history_points: {}, history_end: {}, latest_points: {}, range_duration: {:?}, read_mix: {}, twcs_window: {}, seed: {},\n\
+                 read_consistency: {}, write_consistency: {}, {}",
+                self.key_string_length,
+                self.value_sizes(),
+                self.reads_percentage,
+                self.total_keys,
+                self.point_interval,
+                self.time_bucket,
+                self.history_points,
+                self.history_end,
+                self.latest_points,
+                self.range_duration,
+                self.time_series_read_mix,
+                self.twcs_window,
+                self.seed,
+                self.read_consistency(),
+                self.write_consistency(),
+                self.write_options()
+            );
+        }
+        if self.workload == WorkloadKind::Wide {
+            return write!(
+                f,
+                "workload: wide, key_string_length: {}, value_size: {}, reads_percentage: {}, partitions: {}, rows_per_partition: {},\n\
+                 slice_limit: {}, page_size: {}, read_mix: {}, deletes_percentage: {}, delete_mix: {}, seed: {},\n\
+                 read_consistency: {}, write_consistency: {}, {}",
+                self.key_string_length,
+                self.value_sizes(),
+                self.reads_percentage,
+                self.total_keys,
+                self.rows_per_partition,
+                self.slice_limit,
+                self.page_size,
+                self.wide_read_mix,
+                self.deletes_percentage,
+                self.wide_delete_mix,
+                self.seed,
+                self.read_consistency(),
+                self.write_consistency(),
+                self.write_options()
+            );
+        }
+        write!(
+            f,
+            "key_string_length: {}, value_size: {}, reads_percentage: {}, total_keys: {},\n\
+             key_distribution: {}, read_key_distribution: {}, write_key_distribution: {}, seed: {},\n\
+             read_consistency: {}, write_consistency: {}, {}, batches: {}",
+            self.key_string_length,
+            self.value_sizes(),
+            self.reads_percentage,
+            self.total_keys,
+            self.key_distribution,
+            self.read_key_distribution
+                .as_ref()
+                .unwrap_or(&self.key_distribution),
+            self.write_key_distribution
+                .as_ref()
+                .unwrap_or(&self.key_distribution),
+            self.seed,
+            self.read_consistency(),
+            self.write_consistency(),
+            self.write_options(),
+            match self.batch_size {
+                Some(size) => format!(
+                    "{:?} of {} rows, {:?} grouping",
+                    self.batch_type, size, self.batch_grouping
+                ),
+                None => "none".to_string(),
+            }
+        )
+    }
+}
+
+impl RunArgs {
+    /// Build the workload of the benchmark, with verified reads if requested
+    pub fn build_workload(&self, profile: Option<Profile>) -> Result<Box<dyn Workload>> {
+        if let Some(rate) = self.rate {
+            if !(rate.is_finite() && rate > 0.0) {
+                bail!("--rate must be greater than 0");
+            }
+        }
+        if self.executors_count > self.workload.total_keys {
+            bail!("Executors work on disjoint slices of the keys, --total-keys must be at least --executors-count");
+        }
+        if self.workload.replay.is_some() {
+            if self.executors_count > 1 {
+                bail!("Every executor would replay the whole trace, run it with a single executor");
+            }
+            if self.rate.is_some() || self.verify {
+                bail!("--replay keeps the recorded timing and cannot be combined with --rate or --verify");
+            }
+        }
+        match (profile, self.verify) {
+            (_, true) if self.workload.ttl.is_some() => {
+                bail!("--verify cannot be combined with --ttl, expired rows would count as missing")
+            }
+            (None, true) if self.workload.workload == WorkloadKind::KeyValue => {
+                self.workload.validate()?;
+                Ok(Box::new(self.workload.key_value().with_verified_reads()))
+            }
+            (profile, false) => self.workload.build(profile),
+            (_, true) => bail!("--verify is only supported by the key/value workload"),
+        }
+    }
+}
+
+impl PopulateArgs {
+    /// Population settings of the dataset described by `WorkloadArgs::dataset`
+    pub fn config(&self, report_period: Duration, dataset: String) -> Result<PopulateConfig> {
+        if let Some(rate) = self.populate_rate {
+            if !(rate.is_finite() && rate > 0.0) {
+                bail!("--populate-rate must be greater than 0");
+            }
+        }
+        Ok(PopulateConfig {
+            concurrency: self.populate_concurrency,
+            rate: self.populate_rate,
+            checkpoint: self.checkpoint.clone(),
+            resume: self.resume,
+            dataset,
+            report_period,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Args {
+        Args::try_parse_from(["scylla-perf"].iter().chain(args)).unwrap()
+    }
+
+    fn search_args(args: &[&str]) -> SearchArgs {
+        match parse_args(&[&["search"], args].concat()).command {
+            Command::Search(args) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    fn run_args(args: &[&str]) -> RunArgs {
+        match parse_args(&[&["run"], args].concat()).command {
+            Command::Run(args) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn validates_the_rate() {
+        assert!(run_args(&["--rate", "100"]).build_workload(None).is_ok());
+        for rate in ["0", "-1", "NaN", "inf"] {
+            let args = run_args(&[&format!("--rate={}", rate)]);
+            assert!(args.build_workload(None).is_err(), "{} was accepted", rate);
+        }
+    }
+
+    #[test]
+    fn validates_the_population_rate() {
+        let config = |rate: &str| {
+            let args = run_args(&[&format!("--populate-rate={}", rate)]);
+            args.populate_args
+                .config(Duration::from_secs(1), String::new())
+        };
+        assert!(config("1000").is_ok());
+        for rate in ["0", "-1", "NaN", "inf"] {
+            assert!(config(rate).is_err(), "{} was accepted", rate);
+        }
+    }
+
+    #[test]
+    fn validates_search_options() {
+        assert!(search_args(&[]).validate().is_ok());
+        for args in [
+            &["--min-rate", "0"][..],
+            &["--min-rate=-5"],
+            &["--min-rate", "NaN"],
+            &["--min-rate", "2000", "--max-rate", "1000"],
+            &["--max-rate", "inf"],
+            &["--precision", "0"],
+            &["--precision=-0.1"],
+            &["--slo-percentile", "0"],
+            &["--slo-percentile", "100.5"],
+        ] {
+            assert!(
+                search_args(args).validate().is_err(),
+                "{:?} was accepted",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn validates_schema_names() {
+        assert!(run_args(&["--keyspace", "perf_2", "--table", "Items"])
+            .build_workload(None)
+            .is_ok());
+        for args in [
+            &["--keyspace", ""][..],
+            &["--keyspace", "perf; DROP KEYSPACE system"],
+            &["--keyspace", "perf.items"],
+            &["--table", "items\""],
+            &["--table", "items table"],
+        ] {
+            assert!(
+                run_args(args).build_workload(None).is_err(),
+                "{:?} was accepted",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn only_wide_supports_deletes() {
+        let build = |args: &[&str]| run_args(args).build_workload(None);
+        assert!(build(&["-w", "wide", "--deletes-percentage", "0.1"]).is_ok());
+        assert!(build(&["-w", "key-value", "--deletes-percentage", "0"]).is_ok());
+        for workload in ["key-value", "counter", "time-series"] {
+            let args = ["-w", workload, "--deletes-percentage", "0.1"];
+            assert!(build(&args).is_err(), "{} accepted deletes", workload);
+        }
+    }
+}
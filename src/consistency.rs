@@ -0,0 +1,145 @@
+use anyhow::{bail, Result};
+use scylla::prepared_statement::PreparedStatement;
+use scylla::statement::{Consistency, SerialConsistency};
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// Consistency level of a statement, parsed from its CQL name, e.g. `LOCAL_QUORUM` or `one`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ConsistencyLevel(pub Consistency);
+
+/// Serial consistency level of conditional statements, `SERIAL` or `LOCAL_SERIAL`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct SerialConsistencyLevel(pub SerialConsistency);
+
+/// Consistency settings of a statement, unset levels keep the driver defaults
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StatementConsistency {
+    pub consistency: Option<ConsistencyLevel>,
+    pub serial_consistency: Option<SerialConsistencyLevel>,
+}
+
+impl StatementConsistency {
+    pub fn apply(&self, statement: &mut PreparedStatement) {
+        if let Some(ConsistencyLevel(consistency)) = self.consistency {
+            statement.set_consistency(consistency);
+        }
+        if let Some(SerialConsistencyLevel(serial)) = self.serial_consistency {
+            statement.set_serial_consistency(Some(serial));
+        }
+    }
+}
+
+const CONSISTENCY_NAMES: [(Consistency, &str); 11] = [
+    (Consistency::Any, "ANY"),
+    (Consistency::One, "ONE"),
+    (Consistency::Two, "TWO"),
+    (Consistency::Three, "THREE"),
+    (Consistency::Quorum, "QUORUM"),
+    (Consistency::All, "ALL"),
+    (Consistency::LocalQuorum, "LOCAL_QUORUM"),
+    (Consistency::EachQuorum, "EACH_QUORUM"),
+    (Consistency::LocalOne, "LOCAL_ONE"),
+    (Consistency::Serial, "SERIAL"),
+    (Consistency::LocalSerial, "LOCAL_SERIAL"),
+];
+
+/// Number of consistency levels, `ConsistencyLevel::index` is below it
+pub const CONSISTENCY_LEVELS: usize = CONSISTENCY_NAMES.len();
+
+impl ConsistencyLevel {
+    /// Protocol code of the level, for use as an array index
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn from_index(index: usize) -> Option<ConsistencyLevel> {
+        Consistency::try_from(index as u16)
+            .ok()
+            .map(ConsistencyLevel)
+    }
+}
+
+impl FromStr for ConsistencyLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match CONSISTENCY_NAMES
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(s))
+        {
+            Some((consistency, _)) => Ok(ConsistencyLevel(*consistency)),
+            None => bail!(
+                "Unknown consistency level '{}', available: {}",
+                s,
+                CONSISTENCY_NAMES.map(|(_, name)| name).join(", ")
+            ),
+        }
+    }
+}
+
+impl TryFrom<String> for ConsistencyLevel {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for ConsistencyLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (_, name) = CONSISTENCY_NAMES
+            .iter()
+            .find(|(consistency, _)| *consistency == self.0)
+            .unwrap();
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for SerialConsistencyLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "SERIAL" => Ok(SerialConsistencyLevel(SerialConsistency::Serial)),
+            "LOCAL_SERIAL" => Ok(SerialConsistencyLevel(SerialConsistency::LocalSerial)),
+            _ => bail!(
+                "Unknown serial consistency level '{}', available: SERIAL, LOCAL_SERIAL",
+                s
+            ),
+        }
+    }
+}
+
+impl TryFrom<String> for SerialConsistencyLevel {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for SerialConsistencyLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            SerialConsistency::Serial => write!(f, "SERIAL"),
+            SerialConsistency::LocalSerial => write!(f, "LOCAL_SERIAL"),
+        }
+    }
+}
+
+impl fmt::Display for StatementConsistency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.consistency {
+            Some(consistency) => write!(f, "{}", consistency)?,
+            None => write!(f, "default")?,
+        }
+        if let Some(serial) = self.serial_consistency {
+            write!(f, "/{}", serial)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,589 @@
+use anyhow::{anyhow, bail, Context, Result};
+use rand::distributions::WeightedIndex;
+use rand::Rng;
+use rand_distr::{Distribution, LogNormal, Normal, Zipf};
+use serde::Deserialize;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How benchmark operations pick keys out of the key set.
+///
+/// Parsed from `name` or `name:param1,param2`:
+/// - `uniform` - every key is equally likely
+/// - `zipfian:<theta>` - key `0` is the most popular one, skew grows with theta (default 0.99)
+/// - `gaussian:<stddev>` - normal distribution around the middle of the key set,
+///   stddev is a fraction of the key set size (default 0.1)
+/// - `latest:<theta>` - zipfian skewed towards the most recently inserted keys
+/// - `sequential` - keys are visited in order, wrapping around at the end
+/// - `hotspot:<ops>,<keys>` - fraction `ops` of operations go to fraction `keys` of the keys,
+///   e.g. `hotspot:0.9,0.1` sends 90% of operations to 10% of the keys
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum KeyDistribution {
+    Uniform,
+    Zipfian { theta: f64 },
+    Gaussian { stddev: f64 },
+    Latest { theta: f64 },
+    Sequential,
+    Hotspot { ops: f64, keys: f64 },
+}
+
+impl FromStr for KeyDistribution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, params) = match s.split_once(':') {
+            Some((name, params)) => (name, Some(params)),
+            None => (s, None),
+        };
+        let params: Vec<f64> = match params {
+            Some(params) => params
+                .split(',')
+                .map(|p| {
+                    p.trim()
+                        .parse::<f64>()
+                        .map_err(|e| anyhow!("Invalid parameter '{}' of '{}': {}", p, s, e))
+                })
+                .collect::<Result<_>>()?,
+            None => Vec::new(),
+        };
+        let param = |i: usize, default: f64| params.get(i).copied().unwrap_or(default);
+        let distribution = match name {
+            "uniform" => KeyDistribution::Uniform,
+            "zipfian" => KeyDistribution::Zipfian {
+                theta: param(0, 0.99),
+            },
+            "gaussian" => KeyDistribution::Gaussian {
+                stddev: param(0, 0.1),
+            },
+            "latest" => KeyDistribution::Latest {
+                theta: param(0, 0.99),
+            },
+            "sequential" => KeyDistribution::Sequential,
+            "hotspot" => KeyDistribution::Hotspot {
+                ops: param(0, 0.8),
+                keys: param(1, 0.2),
+            },
+            _ => bail!(
+                "Unknown key distribution '{}', available: uniform, zipfian, gaussian, latest, sequential, hotspot",
+                name
+            ),
+        };
+        distribution.validate()?;
+        Ok(distribution)
+    }
+}
+
+impl TryFrom<String> for KeyDistribution {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for KeyDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyDistribution::Uniform => write!(f, "uniform"),
+            KeyDistribution::Zipfian { theta } => write!(f, "zipfian:{}", theta),
+            KeyDistribution::Gaussian { stddev } => write!(f, "gaussian:{}", stddev),
+            KeyDistribution::Latest { theta } => write!(f, "latest:{}", theta),
+            KeyDistribution::Sequential => write!(f, "sequential"),
+            KeyDistribution::Hotspot { ops, keys } => write!(f, "hotspot:{},{}", ops, keys),
+        }
+    }
+}
+
+impl KeyDistribution {
+    fn validate(&self) -> Result<()> {
+        match self {
+            KeyDistribution::Zipfian { theta } | KeyDistribution::Latest { theta } => {
+                if !(theta.is_finite() && *theta > 0.0) {
+                    bail!("Zipfian theta must be a finite number greater than 0");
+                }
+            }
+            KeyDistribution::Gaussian { stddev } => {
+                if !(stddev.is_finite() && *stddev > 0.0) {
+                    bail!("Gaussian stddev must be a finite number greater than 0");
+                }
+            }
+            KeyDistribution::Hotspot { ops, keys } => {
+                if !(0.0..=1.0).contains(ops) || !(0.0..=1.0).contains(keys) {
+                    bail!("Hotspot fractions must be between 0.0 and 1.0");
+                }
+            }
+            KeyDistribution::Uniform | KeyDistribution::Sequential => {}
+        }
+        Ok(())
+    }
+
+    /// Build a sampler picking key indexes from `0..keys`
+    pub fn sampler(&self, keys: usize) -> KeySampler {
+        let keys = keys.max(1);
+        let kind = match self {
+            KeyDistribution::Uniform => SamplerKind::Uniform,
+            KeyDistribution::Zipfian { theta } => {
+                SamplerKind::Zipfian(Zipf::new(keys as u64, *theta).unwrap())
+            }
+            KeyDistribution::Latest { theta } => {
+                SamplerKind::Latest(Zipf::new(keys as u64, *theta).unwrap())
+            }
+            KeyDistribution::Gaussian { stddev } => {
+                SamplerKind::Gaussian(Normal::new(keys as f64 / 2.0, keys as f64 * stddev).unwrap())
+            }
+            KeyDistribution::Sequential => SamplerKind::Sequential(AtomicUsize::new(0)),
+            KeyDistribution::Hotspot { ops, keys: hot } => SamplerKind::Hotspot {
+                ops: *ops,
+                hot_keys: ((keys as f64 * hot) as usize).clamp(1, keys),
+            },
+        };
+        KeySampler {
+            distribution: self.clone(),
+            offset: 0,
+            keys,
+            kind,
+        }
+    }
+}
+
+/// Part of the keys an executor works on when several executors share one dataset,
+/// `index` out of `count` contiguous slices of the key set
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeySlice {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Default for KeySlice {
+    /// The whole key set
+    fn default() -> Self {
+        KeySlice { index: 0, count: 1 }
+    }
+}
+
+impl KeySlice {
+    /// Indexes of the slice out of `0..keys`. Slices are disjoint, cover every key and
+    /// differ in size by at most one
+    pub fn range(&self, keys: usize) -> Range<usize> {
+        keys * self.index / self.count..keys * (self.index + 1) / self.count
+    }
+}
+
+/// Picks key indexes according to a [`KeyDistribution`], shared by all tasks of an executor
+pub struct KeySampler {
+    distribution: KeyDistribution,
+    /// First index of the sampled keys, non-zero for a slice of the key set
+    offset: usize,
+    keys: usize,
+    kind: SamplerKind,
+}
+
+enum SamplerKind {
+    Uniform,
+    Zipfian(Zipf<f64>),
+    Latest(Zipf<f64>),
+    Gaussian(Normal<f64>),
+    Sequential(AtomicUsize),
+    Hotspot { ops: f64, hot_keys: usize },
+}
+
+impl KeySampler {
+    /// Sampler with the same distribution over the given slice of the keys of this one.
+    /// The distribution applies within the slice, e.g. with `zipfian` the first key of
+    /// every slice is the most popular one
+    pub fn slice(&self, slice: KeySlice) -> KeySampler {
+        let range = slice.range(self.keys);
+        KeySampler {
+            offset: self.offset + range.start,
+            ..self.distribution.sampler(range.len())
+        }
+    }
+
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        self.offset + self.sample_index(rng)
+    }
+
+    fn sample_index<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        match &self.kind {
+            SamplerKind::Uniform => rng.gen_range(0..self.keys),
+            SamplerKind::Zipfian(zipf) => zipf.sample(rng) as usize - 1,
+            SamplerKind::Latest(zipf) => self.keys - zipf.sample(rng) as usize,
+            SamplerKind::Gaussian(normal) => {
+                (normal.sample(rng).max(0.0) as usize).min(self.keys - 1)
+            }
+            SamplerKind::Sequential(next) => next.fetch_add(1, Ordering::Relaxed) % self.keys,
+            SamplerKind::Hotspot { ops, hot_keys } => {
+                if rng.gen::<f64>() < *ops || *hot_keys == self.keys {
+                    rng.gen_range(0..*hot_keys)
+                } else {
+                    rng.gen_range(*hot_keys..self.keys)
+                }
+            }
+        }
+    }
+}
+
+/// Largest value sampled from the normal and log-normal value size distributions unless
+/// configured, the largest mutation Scylla accepts with the default commit log segment size
+const DEFAULT_MAX_VALUE_SIZE: usize = 16 << 20;
+
+/// Size of generated values in bytes.
+///
+/// Parsed from `name:param1,param2`:
+/// - `fixed:<size>` - every value has the same size
+/// - `uniform:<min>,<max>` - sizes uniformly distributed between min and max, inclusive
+/// - `normal:<mean>,<stddev>[,<max>]` - normally distributed sizes, negative samples become 0
+/// - `lognormal:<median>,<sigma>[,<max>]` - log-normally distributed sizes, a long tail of large
+///   values
+/// - `histogram:<path>` - sizes taken from a file with `<size> <weight>` lines,
+///   empty lines and lines starting with `#` are skipped
+///
+/// Samples of the unbounded distributions are clamped to `max`, [`DEFAULT_MAX_VALUE_SIZE`]
+/// if not given, so a long tail cannot allocate arbitrarily large values
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ValueSizeDistribution {
+    Fixed(usize),
+    Uniform {
+        min: usize,
+        max: usize,
+    },
+    Normal {
+        dist: Normal<f64>,
+        max: usize,
+    },
+    LogNormal {
+        median: f64,
+        sigma: f64,
+        dist: LogNormal<f64>,
+        max: usize,
+    },
+    Histogram {
+        path: String,
+        sizes: Vec<usize>,
+        weights: WeightedIndex<f64>,
+    },
+}
+
+impl FromStr for ValueSizeDistribution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse_with_max(s, DEFAULT_MAX_VALUE_SIZE)
+    }
+}
+
+impl ValueSizeDistribution {
+    /// Parse the distribution, with `default_max` as the max of the normal and log-normal
+    /// distributions that do not give one, for other quantities drawn from the same forms
+    pub fn parse_with_max(s: &str, default_max: usize) -> Result<Self> {
+        let (name, params) = s.split_once(':').unwrap_or((s, ""));
+        if name == "histogram" {
+            return Self::load_histogram(params);
+        }
+        let params: Vec<f64> = params
+            .split(',')
+            .filter(|p| !p.trim().is_empty())
+            .map(|p| {
+                p.trim()
+                    .parse::<f64>()
+                    .map_err(|e| anyhow!("Invalid parameter '{}' of '{}': {}", p, s, e))
+            })
+            .collect::<Result<_>>()?;
+        let max_params = match name {
+            "fixed" => 1,
+            "uniform" => 2,
+            "normal" | "lognormal" => 3,
+            _ => bail!(
+                "Unknown value size distribution '{}', available: fixed, uniform, normal, lognormal, histogram",
+                name
+            ),
+        };
+        if params.len() > max_params {
+            bail!(
+                "Value size distribution '{}' takes at most {} parameters",
+                s,
+                max_params
+            );
+        }
+        let param = |i: usize| {
+            params
+                .get(i)
+                .copied()
+                .ok_or_else(|| anyhow!("Value size distribution '{}' needs more parameters", s))
+        };
+        // sizes are whole numbers of bytes
+        let size = |value: f64| {
+            if !(value.is_finite() && value >= 0.0 && value.fract() == 0.0) {
+                bail!(
+                    "Invalid size {} of '{}', expected a number of bytes",
+                    value,
+                    s
+                );
+            }
+            Ok(value as usize)
+        };
+        let max = || match params.get(2) {
+            Some(max) => size(*max),
+            None => Ok(default_max),
+        };
+        let distribution = match name {
+            "fixed" => ValueSizeDistribution::Fixed(size(param(0)?)?),
+            "uniform" => {
+                let (min, max) = (size(param(0)?)?, size(param(1)?)?);
+                if min > max {
+                    bail!("Uniform value size minimum is greater than maximum");
+                }
+                ValueSizeDistribution::Uniform { min, max }
+            }
+            "normal" => {
+                let (mean, stddev) = (param(0)?, param(1)?);
+                if !(mean.is_finite() && stddev.is_finite() && stddev >= 0.0) {
+                    bail!("Normal value size needs a finite mean and a non-negative stddev");
+                }
+                ValueSizeDistribution::Normal {
+                    dist: Normal::new(mean, stddev)?,
+                    max: max()?,
+                }
+            }
+            _ => {
+                let (median, sigma) = (param(0)?, param(1)?);
+                if !(median.is_finite() && median > 0.0) {
+                    bail!("Log-normal value size median must be greater than 0");
+                }
+                if !(sigma.is_finite() && sigma >= 0.0) {
+                    bail!("Log-normal value size sigma must not be negative");
+                }
+                ValueSizeDistribution::LogNormal {
+                    median,
+                    sigma,
+                    dist: LogNormal::new(median.ln(), sigma)?,
+                    max: max()?,
+                }
+            }
+        };
+        Ok(distribution)
+    }
+}
+
+impl fmt::Display for ValueSizeDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValueSizeDistribution::Fixed(size) => write!(f, "fixed:{}", size),
+            ValueSizeDistribution::Uniform { min, max } => write!(f, "uniform:{},{}", min, max),
+            ValueSizeDistribution::Normal { dist, max } => {
+                write!(f, "normal:{},{},{}", dist.mean(), dist.std_dev(), max)
+            }
+            ValueSizeDistribution::LogNormal {
+                median, sigma, max, ..
+            } => {
+                write!(f, "lognormal:{},{},{}", median, sigma, max)
+            }
+            ValueSizeDistribution::Histogram { path, .. } => write!(f, "histogram:{}", path),
+        }
+    }
+}
+
+impl ValueSizeDistribution {
+    fn load_histogram(path: &str) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read value size histogram {}", path))?;
+        let mut sizes = Vec::new();
+        let mut weights = Vec::new();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split(|c: char| c.is_whitespace() || c == ',');
+            let mut field = |name: &str| {
+                fields
+                    .find(|f| !f.is_empty())
+                    .ok_or_else(|| anyhow!("Missing {} in histogram line '{}'", name, line))
+            };
+            sizes.push(
+                field("size")?
+                    .parse::<usize>()
+                    .with_context(|| format!("Invalid size in histogram line '{}'", line))?,
+            );
+            weights.push(
+                field("weight")?
+                    .parse::<f64>()
+                    .with_context(|| format!("Invalid weight in histogram line '{}'", line))?,
+            );
+        }
+        let weights = WeightedIndex::new(weights)
+            .with_context(|| format!("Invalid weights in value size histogram {}", path))?;
+        Ok(ValueSizeDistribution::Histogram {
+            path: path.to_string(),
+            sizes,
+            weights,
+        })
+    }
+
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        match self {
+            ValueSizeDistribution::Fixed(size) => *size,
+            ValueSizeDistribution::Uniform { min, max } => rng.gen_range(*min..=*max),
+            ValueSizeDistribution::Normal { dist, max } => {
+                (dist.sample(rng).max(0.0) as usize).min(*max)
+            }
+            ValueSizeDistribution::LogNormal { dist, max, .. } => {
+                (dist.sample(rng) as usize).min(*max)
+            }
+            ValueSizeDistribution::Histogram { sizes, weights, .. } => sizes[weights.sample(rng)],
+        }
+    }
+}
+
+impl TryFrom<String> for ValueSizeDistribution {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_key_distributions() {
+        assert_eq!(
+            "uniform".parse::<KeyDistribution>().unwrap(),
+            KeyDistribution::Uniform
+        );
+        assert_eq!(
+            "zipfian".parse::<KeyDistribution>().unwrap(),
+            KeyDistribution::Zipfian { theta: 0.99 }
+        );
+        assert_eq!(
+            "latest:1.2".parse::<KeyDistribution>().unwrap(),
+            KeyDistribution::Latest { theta: 1.2 }
+        );
+        assert_eq!(
+            "hotspot:0.9,0.1".parse::<KeyDistribution>().unwrap(),
+            KeyDistribution::Hotspot {
+                ops: 0.9,
+                keys: 0.1
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_key_distributions() {
+        for s in [
+            "pareto",
+            "zipfian:nan",
+            "zipfian:0",
+            "zipfian:-1",
+            "zipfian:inf",
+            "gaussian:nan",
+            "gaussian:0",
+            "hotspot:1.5,0.1",
+            "uniform:x",
+        ] {
+            assert!(s.parse::<KeyDistribution>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn parses_value_size_distributions() {
+        let parse = |s: &str| s.parse::<ValueSizeDistribution>().unwrap().to_string();
+        assert_eq!(parse("fixed:100"), "fixed:100");
+        assert_eq!(parse("uniform:5,6"), "uniform:5,6");
+        assert_eq!(
+            parse("normal:100,10"),
+            format!("normal:100,10,{}", 16 << 20)
+        );
+        assert_eq!(parse("lognormal:1024,2,65536"), "lognormal:1024,2,65536");
+    }
+
+    #[test]
+    fn clamps_value_sizes_to_max() {
+        let distribution: ValueSizeDistribution = "lognormal:1024,20,4096".parse().unwrap();
+        let mut rng = rand::thread_rng();
+        for _ in 0..1000 {
+            assert!(distribution.sample(&mut rng) <= 4096);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_value_size_distributions() {
+        for s in [
+            "fixed:-1",
+            "fixed:1.5",
+            "fixed",
+            "fixed:1,2",
+            "uniform:5,6,7",
+            "uniform:6,5",
+            "normal:100,-1",
+            "normal:100,10,-5",
+            "lognormal:0,1",
+            "lognormal:nan,1",
+            "lognormal:1,1,2,3",
+            "exponential:1",
+        ] {
+            assert!(s.parse::<ValueSizeDistribution>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn slices_cover_the_keys_when_not_divisible() {
+        for (keys, count) in [(10, 3), (1000, 7), (5, 5), (7, 2)] {
+            let ranges: Vec<_> = (0..count)
+                .map(|index| KeySlice { index, count }.range(keys))
+                .collect();
+            assert_eq!(ranges[0].start, 0);
+            assert_eq!(ranges[count - 1].end, keys);
+            for pair in ranges.windows(2) {
+                assert_eq!(pair[0].end, pair[1].start);
+            }
+            let sizes: Vec<usize> = ranges.iter().map(|r| r.len()).collect();
+            let (min, max) = (sizes.iter().min().unwrap(), sizes.iter().max().unwrap());
+            assert!(
+                max - min <= 1,
+                "{} keys in {} slices: {:?}",
+                keys,
+                count,
+                sizes
+            );
+        }
+        assert_eq!(KeySlice { index: 2, count: 3 }.range(10), 6..10);
+    }
+
+    #[test]
+    fn sliced_samplers_stay_in_their_slice() {
+        let rng = &mut rand::thread_rng();
+        for distribution in [
+            "uniform",
+            "zipfian",
+            "latest",
+            "gaussian",
+            "sequential",
+            "hotspot",
+        ] {
+            let sampler = distribution
+                .parse::<KeyDistribution>()
+                .unwrap()
+                .sampler(100);
+            for index in 0..3 {
+                let range = KeySlice { index, count: 3 }.range(100);
+                let slice = sampler.slice(KeySlice { index, count: 3 });
+                for _ in 0..1000 {
+                    let key = slice.sample(rng);
+                    assert!(
+                        range.contains(&key),
+                        "{} sampled {} out of {:?}",
+                        distribution,
+                        key,
+                        range
+                    );
+                }
+            }
+        }
+    }
+}
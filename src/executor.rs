@@ -1,195 +1,230 @@
-use crate::reporter::{QueryType, Reporter, SimpleReporter};
-use anyhow::Result;
-use rand::distributions::{Alphanumeric, DistString};
-use rand::{random, Rng};
-use scylla::prepared_statement::PreparedStatement;
+use crate::distribution::KeySlice;
+use crate::reporter::Reporter;
+use crate::verify::{read_initial_values, verify};
+use crate::workload::{Verification, Workload};
+use anyhow::{bail, Result};
 use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
-use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::oneshot::Receiver;
 use tokio::sync::oneshot::Sender;
-use tokio::sync::{broadcast, oneshot, Mutex};
+use tokio::sync::{oneshot, Semaphore};
+use tokio::time::Instant;
 
-pub struct Executor {
-    concurrency: usize,
-    reads_percentage: f32,
-    reporter: Arc<SimpleReporter>,
-    key_values_range: Vec<KeyValue>,
-    dont_drop_test_keyspace: bool,
+pub struct ExecutorConfig {
+    pub concurrency: usize,
+    /// Target rate in operations per second, finite and above 0. `None` means closed-loop
+    /// mode: a new request is sent as soon as one of the in-flight requests finishes
+    pub rate: Option<f64>,
+    /// Keys of the dataset this executor works on, see `Workload::set_slice`
+    pub slice: KeySlice,
+    /// How long to wait for the in-flight requests once stopped, `None` to wait for all
+    pub drain_timeout: Option<Duration>,
+    /// Stop the benchmark as soon as a verified read finds a missing row or a wrong value
+    pub stop_on_corruption: bool,
+    /// Verify the slice once the benchmark is over, if the workload tracks the values it
+    /// expects, see `Workload::verify_after_run`
+    pub verify_after_run: bool,
+    /// Period of the progress reports of the verification after the benchmark
+    pub report_period: Duration,
 }
 
-pub struct KeyValue(String, Vec<u8>);
-
-impl Clone for KeyValue {
-    fn clone(&self) -> Self {
-        KeyValue(self.0.clone(), self.1.clone())
-    }
+pub struct Executor {
+    concurrency: usize,
+    rate: Option<f64>,
+    workload: Box<dyn Workload>,
+    reporter: Arc<dyn Reporter>,
+    slice: KeySlice,
+    drain_timeout: Option<Duration>,
+    stop_on_corruption: bool,
+    verify_after_run: bool,
+    report_period: Duration,
 }
 
 impl Executor {
     pub fn new(
-        concurrency: usize,
-        key_string_length: usize,
-        value_blob_size: usize,
-        reads_percentage: f32,
-        total_keys: usize,
-        reporter: Arc<SimpleReporter>,
-        dont_drop_test_keyspace: bool,
+        config: ExecutorConfig,
+        workload: Box<dyn Workload>,
+        reporter: Arc<dyn Reporter>,
     ) -> Executor {
-        if reads_percentage < 0.0 || reads_percentage > 1.0 {
-            panic!("Reads percentage must be between 0.0 and 1.0");
-        }
         Executor {
-            concurrency,
-            reads_percentage,
-            key_values_range: generate_key_values_range(
-                total_keys,
-                key_string_length,
-                value_blob_size,
-            ),
-            reporter: reporter,
-            dont_drop_test_keyspace,
+            concurrency: config.concurrency,
+            rate: config.rate,
+            workload,
+            reporter,
+            slice: config.slice,
+            drain_timeout: config.drain_timeout,
+            stop_on_corruption: config.stop_on_corruption,
+            verify_after_run: config.verify_after_run,
+            report_period: config.report_period,
         }
     }
 
     pub async fn start(
-        &mut self,
+        mut self,
         session: Arc<GenericSession<CurrentDeserializationApi>>,
-    ) -> Result<(Sender<()>, tokio::task::JoinHandle<()>)> {
+    ) -> Result<(Sender<()>, tokio::task::JoinHandle<Result<()>>)> {
         println!("Starting executor...");
-        let create_keyspace = "CREATE KEYSPACE IF NOT EXISTS test WITH REPLICATION = { 'class' : 'SimpleStrategy', 'replication_factor' : 1 }";
-        let create_table =
-            "CREATE TABLE IF NOT EXISTS test.test (key text PRIMARY KEY, value blob);";
-        session.query_unpaged(create_keyspace, &[]).await?;
-        session.query_unpaged(create_table, &[]).await?;
-        let prepared_read = session
-            .prepare("SELECT * FROM test.test WHERE key = ?")
-            .await?;
-        let prepared_write = session
-            .prepare("INSERT INTO test.test (key, value) VALUES (?, ?)")
-            .await?;
+        // the schema and the dataset are set up once for all executors, see `SchemaLifecycle`
+        self.workload.set_slice(self.slice);
+        self.workload.prepare(&session).await?;
         let (tx_stop_coordinator, mut rx_stop_coordinator): (Sender<()>, Receiver<()>) =
             oneshot::channel();
-        println!("Inserting initial key-value pairs...");
-        for kv in &self.key_values_range {
-            perform_write(session.clone(), prepared_write.clone(), kv.clone()).await?;
+        let workload: Arc<dyn Workload> = Arc::from(self.workload);
+        let concurrency = self.concurrency;
+        let rate = self.rate;
+        let reporter_clone = self.reporter.clone();
+        let slice = self.slice;
+        let drain_timeout = self.drain_timeout;
+        let stop_on_corruption = self.stop_on_corruption;
+        let verify_after_run = self.verify_after_run && workload.verify_after_run();
+        if verify_after_run {
+            // the verification compares with the values the slice has before the benchmark
+            println!("Reading the initial values of the keys of the executor...");
+            read_initial_values(
+                workload.clone(),
+                session.clone(),
+                slice.range(workload.population_size()),
+                concurrency,
+            )
+            .await?;
         }
-        println!("Done inserting initial key-value pairs");
         println!("Starting queries...");
-        let concurrency = self.concurrency.clone();
-        let key_values_range = self.key_values_range.clone();
-        let reads_percentage = self.reads_percentage.clone();
-        let reporter_clone = self.reporter.clone();
-        let dont_drop_test_keyspace_clone = self.dont_drop_test_keyspace.clone();
+        let report_period = self.report_period;
+        let corrupted = Arc::new(AtomicBool::new(false));
         let coordinator_thread = tokio::task::spawn(async move {
-            let current_concurrency = Arc::new(AtomicUsize::new(0));
+            let in_flight = Arc::new(Semaphore::new(concurrency));
+            let schedule_start = Instant::now();
+            let mut issued: u64 = 0;
+            let trace_len = workload.trace().map(|trace| trace.len());
+            // set once the stop signal was received while waiting, the receiver must not be
+            // polled again after that
+            let mut stop_requested = false;
             loop {
-                let stop = rx_stop_coordinator.try_recv();
-                // if stop signal received, stop executor
-                if stop.is_ok() {
+                let stop = stop_requested || rx_stop_coordinator.try_recv().is_ok();
+                let corruption_found = corrupted.load(Ordering::Relaxed);
+                let trace_replayed = trace_len.is_some_and(|len| issued as usize >= len);
+                if trace_replayed {
+                    println!("Issued every operation of the trace");
+                }
+                // if stop signal received, corrupted data was read or the trace is over, stop executor
+                if stop || corruption_found || trace_replayed {
                     println!("Coordinator received stop signal, waiting for concurrent tasks to finish...");
-                    while current_concurrency.load(std::sync::atomic::Ordering::Relaxed) > 0 {
-                        tokio::time::sleep(Duration::from_millis(100)).await;
+                    // the drain and the verification below do not count into the rates
+                    reporter_clone.stop();
+                    let drained = match drain_timeout {
+                        Some(timeout) => tokio::time::timeout(
+                            timeout,
+                            in_flight.acquire_many(concurrency as u32),
+                        )
+                        .await
+                        .is_ok(),
+                        None => in_flight.acquire_many(concurrency as u32).await.is_ok(),
+                    };
+                    let pending = concurrency - in_flight.available_permits();
+                    if !drained {
+                        println!(
+                            "{} requests still in flight after {:?}, not waiting for them",
+                            pending,
+                            drain_timeout.unwrap_or_default()
+                        );
                     }
-                    if !dont_drop_test_keyspace_clone {
-                        println!("Dropping test keyspace...");
-                        let res = session.query_unpaged("DROP KEYSPACE test", &[]).await;
-                        if res.is_err() {
-                            panic!("Error dropping keyspace: {:?}", res.err());
-                        }
-                        println!("Dropped test keyspace");
+                    // the pending requests could still change the rows being verified
+                    let verified = if verify_after_run && !corruption_found && drained {
+                        println!(
+                            "Verifying the keys of the executor against the expected values..."
+                        );
+                        verify(
+                            workload.clone(),
+                            session.clone(),
+                            slice.range(workload.population_size()),
+                            concurrency,
+                            report_period,
+                        )
+                        .await?
+                        .is_ok()
+                    } else {
+                        true
+                    };
+                    if corruption_found {
+                        bail!("Stopped on the first corrupted read");
+                    }
+                    if !verified {
+                        bail!("Verification after the benchmark failed");
                     }
-                    break;
+                    // a run whose verification was skipped must not pass as verified
+                    if verify_after_run && !drained {
+                        bail!(
+                            "The keys of the executor were not verified, {} requests were still in flight",
+                            pending
+                        );
+                    }
+                    return Ok(());
                 }
-                // if there are still queries to be sent
-                while current_concurrency.load(std::sync::atomic::Ordering::Relaxed) < concurrency {
-                    current_concurrency.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    let session_clone = session.clone();
-                    let kvs = key_values_range.clone();
-                    let reads_percentage = reads_percentage.clone();
-                    let pread = prepared_read.clone();
-                    let pwrite = prepared_write.clone();
-                    let current_concurrency_clone = Arc::clone(&current_concurrency);
-                    let reporter_clone_clone = reporter_clone.clone();
-                    tokio::spawn(async move {
-                        let kv = kvs.get(rand::thread_rng().gen_range(0..kvs.len())).unwrap();
-                        let rng = random::<f32>();
-                        let res = if rng < reads_percentage {
-                            (
-                                QueryType::Read,
-                                perform_read(session_clone, pread, kv.clone()).await,
-                            )
-                        } else {
-                            (
-                                QueryType::Write,
-                                perform_write(session_clone, pwrite, kv.clone()).await,
-                            )
-                        };
-                        if res.1.is_err() {
-                            println!("Error executing query: {:?}, {:?}", res.0, res.1.err());
-                        } else {
-                            let q_type = res.0;
-                            let elapsed = res.1.unwrap();
-                            reporter_clone_clone.report_results(q_type, elapsed);
+                // in fixed-rate and replay mode wait for the next slot of the timetable, the
+                // slot is kept even if the request is delayed by the concurrency limit
+                let scheduled_at = match (workload.trace(), rate) {
+                    (Some(trace), _) => Some(schedule_start + trace.entry(issued as usize).offset),
+                    (None, Some(rate)) => {
+                        Some(schedule_start + Duration::from_secs_f64(issued as f64 / rate))
+                    }
+                    (None, None) => None,
+                };
+                // the waits below can be long with a low rate or a slow cluster, a stop signal
+                // ends them instead of waiting for the next slot or a free permit
+                if let Some(at) = scheduled_at {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(at) => {}
+                        _ = &mut rx_stop_coordinator => {
+                            stop_requested = true;
+                            continue;
                         }
-                        current_concurrency_clone
-                            .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
-                    });
+                    }
                 }
+                let permit = tokio::select! {
+                    permit = in_flight.clone().acquire_owned() => permit.unwrap(),
+                    _ = &mut rx_stop_coordinator => {
+                        stop_requested = true;
+                        continue;
+                    }
+                };
+                let intended_start = scheduled_at.unwrap_or_else(Instant::now);
+                let n = issued as usize;
+                issued += 1;
+                let session_clone = session.clone();
+                let workload_clone = workload.clone();
+                let reporter_clone_clone = reporter_clone.clone();
+                let corrupted_clone = corrupted.clone();
+                tokio::spawn(async move {
+                    let start = Instant::now();
+                    let result = if trace_len.is_some() {
+                        workload_clone.replay(&session_clone, n).await
+                    } else {
+                        workload_clone.next_operation(&session_clone).await
+                    };
+                    match result {
+                        Ok(operation) => {
+                            reporter_clone_clone.report_results(
+                                &operation,
+                                start.elapsed(),
+                                intended_start.elapsed(),
+                            );
+                            if stop_on_corruption
+                                && matches!(
+                                    operation.verification,
+                                    Some(Verification::Missing | Verification::Mismatch)
+                                )
+                            {
+                                corrupted_clone.store(true, Ordering::Relaxed);
+                            }
+                        }
+                        Err(e) => println!("Error executing query: {:?}", e),
+                    }
+                    drop(permit);
+                });
             }
         });
         Ok((tx_stop_coordinator, coordinator_thread))
     }
 }
-
-async fn perform_read(
-    session: Arc<GenericSession<CurrentDeserializationApi>>,
-    ps: PreparedStatement,
-    kv: KeyValue,
-) -> Result<Duration> {
-    let start = tokio::time::Instant::now();
-    let res = session.execute_unpaged(&ps, (kv.0.clone(),)).await;
-    if res.is_err() {
-        Err(anyhow::anyhow!("Row not found"))
-    } else {
-        let elapsed = start.elapsed();
-        Ok(elapsed)
-    }
-}
-async fn perform_write(
-    session: Arc<GenericSession<CurrentDeserializationApi>>,
-    ps: PreparedStatement,
-    kv: KeyValue,
-) -> Result<Duration> {
-    let start = tokio::time::Instant::now();
-    let str: String = kv.0.clone();
-    let vec: &Vec<u8> = &kv.1;
-    let _ = session.execute_unpaged(&ps, (str, vec)).await;
-    let elapsed = start.elapsed();
-    Ok(elapsed)
-}
-
-fn generate_key_values_range(
-    total_keys: usize,
-    key_string_length: usize,
-    value_blob_size: usize,
-) -> Vec<KeyValue> {
-    let string = format!(
-        "Generating {total_keys} key-value pairs, key length: {key_string_length}, value size: {value_blob_size}"
-    );
-    println!("{}", string);
-    let mut key_values_range = Vec::new();
-    let rng = &mut rand::thread_rng();
-    for _ in 0..total_keys {
-        // generate random key string of length key_string_length
-        let key = Alphanumeric.sample_string(rng, key_string_length);
-        // generate random value blob of size value_blob_size
-        let mut value = Vec::new();
-        for _ in 0..value_blob_size {
-            value.push(rng.gen::<u8>());
-        }
-        key_values_range.push(KeyValue(key, value));
-    }
-    key_values_range
-}
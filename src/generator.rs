@@ -0,0 +1,202 @@
+use crate::distribution::ValueSizeDistribution;
+use anyhow::{bail, Result};
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use scylla::frame::response::result::{ColumnType, CqlValue};
+use scylla::frame::value::{
+    Counter, CqlDate, CqlDecimal, CqlDuration, CqlTime, CqlTimestamp, CqlTimeuuid, CqlVarint,
+};
+use serde::Deserialize;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, Ipv4Addr};
+use uuid::Builder;
+
+/// Number of 100ns intervals between the start of the Gregorian calendar (the epoch of
+/// version 1 UUIDs) and the Unix epoch
+const GREGORIAN_TO_UNIX_TICKS: u64 = 0x01B2_1DD2_1381_4000;
+const NANOS_PER_DAY: u64 = 86_400_000_000_000;
+
+/// Settings of a bind-variable generator, all of them are optional
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct GeneratorSpec {
+    /// Length of generated text values
+    pub length: Option<usize>,
+    /// Size of generated blob values in bytes
+    pub size: Option<usize>,
+    /// Distribution of blob sizes, e.g. `lognormal:1024,1.0`, takes precedence over `size`
+    pub size_distribution: Option<ValueSizeDistribution>,
+    /// Number of distinct values, the row index is taken modulo this number
+    pub cardinality: Option<u64>,
+    /// Number of elements of generated list, set and map values
+    pub elements: Option<usize>,
+}
+
+/// Generates values of a single bind variable. Values are derived from the row index,
+/// so the same index always produces the same value and a row written during population
+/// can be found again by the benchmark queries.
+#[derive(Clone, Debug)]
+pub struct ValueGenerator {
+    typ: ColumnType<'static>,
+    spec: GeneratorSpec,
+    seed: u64,
+}
+
+impl ValueGenerator {
+    pub fn new(
+        column: &str,
+        typ: &ColumnType,
+        spec: GeneratorSpec,
+        seed: u64,
+    ) -> Result<ValueGenerator> {
+        if !Self::is_supported(typ) {
+            bail!("Column '{}' has unsupported type {:?}", column, typ);
+        }
+        let mut hasher = DefaultHasher::new();
+        column.hash(&mut hasher);
+        Ok(ValueGenerator {
+            typ: typ.clone().into_owned(),
+            spec,
+            seed: seed ^ hasher.finish(),
+        })
+    }
+
+    fn is_supported(typ: &ColumnType) -> bool {
+        match typ {
+            ColumnType::List(element) | ColumnType::Set(element) => Self::is_supported(element),
+            ColumnType::Map(key, value) => Self::is_supported(key) && Self::is_supported(value),
+            ColumnType::Tuple(elements) => elements.iter().all(Self::is_supported),
+            typ => matches!(
+                typ,
+                ColumnType::Ascii
+                    | ColumnType::Text
+                    | ColumnType::Blob
+                    | ColumnType::Boolean
+                    | ColumnType::TinyInt
+                    | ColumnType::SmallInt
+                    | ColumnType::Int
+                    | ColumnType::BigInt
+                    | ColumnType::Varint
+                    | ColumnType::Counter
+                    | ColumnType::Float
+                    | ColumnType::Double
+                    | ColumnType::Decimal
+                    | ColumnType::Uuid
+                    | ColumnType::Timeuuid
+                    | ColumnType::Timestamp
+                    | ColumnType::Date
+                    | ColumnType::Time
+                    | ColumnType::Duration
+                    | ColumnType::Inet
+            ),
+        }
+    }
+
+    pub fn generate(&self, index: u64) -> CqlValue {
+        let index = match self.spec.cardinality {
+            Some(cardinality) if cardinality > 0 => index % cardinality,
+            _ => index,
+        };
+        let mut rng = index_rng(self.seed, index);
+        self.value(&self.typ, index, &mut rng)
+    }
+
+    /// Value of type `typ` for the row `index`. Scalars are derived from the index, or from
+    /// `rng` when they need randomness, collection elements use indexes drawn from `rng`
+    fn value(&self, typ: &ColumnType, index: u64, rng: &mut StdRng) -> CqlValue {
+        match typ {
+            ColumnType::Ascii => {
+                CqlValue::Ascii(Alphanumeric.sample_string(rng, self.spec.length.unwrap_or(10)))
+            }
+            ColumnType::Text => {
+                CqlValue::Text(Alphanumeric.sample_string(rng, self.spec.length.unwrap_or(10)))
+            }
+            ColumnType::Blob => {
+                let size = match &self.spec.size_distribution {
+                    Some(sizes) => sizes.sample(rng),
+                    None => self.spec.size.unwrap_or(10),
+                };
+                let mut value = vec![0u8; size];
+                rng.fill(&mut value[..]);
+                CqlValue::Blob(value)
+            }
+            ColumnType::Boolean => CqlValue::Boolean(index.is_multiple_of(2)),
+            ColumnType::TinyInt => CqlValue::TinyInt(index as i8),
+            ColumnType::SmallInt => CqlValue::SmallInt(index as i16),
+            ColumnType::Int => CqlValue::Int(index as i32),
+            ColumnType::BigInt => CqlValue::BigInt(index as i64),
+            ColumnType::Varint => {
+                CqlValue::Varint(CqlVarint::from_signed_bytes_be_slice(&index.to_be_bytes()))
+            }
+            ColumnType::Counter => CqlValue::Counter(Counter(1)),
+            ColumnType::Float => CqlValue::Float(index as f32),
+            ColumnType::Double => CqlValue::Double(index as f64),
+            ColumnType::Decimal => CqlValue::Decimal(
+                CqlDecimal::from_signed_be_bytes_slice_and_exponent(&index.to_be_bytes(), 2),
+            ),
+            ColumnType::Uuid => CqlValue::Uuid(Builder::from_random_bytes(rng.gen()).into_uuid()),
+            // same instant as the timestamp of the row, so time ranges select the same rows
+            ColumnType::Timeuuid => CqlValue::Timeuuid(CqlTimeuuid::from(
+                Builder::from_gregorian_timestamp(
+                    GREGORIAN_TO_UNIX_TICKS + index * 10_000,
+                    rng.gen(),
+                    &rng.gen(),
+                )
+                .into_uuid(),
+            )),
+            ColumnType::Timestamp => CqlValue::Timestamp(CqlTimestamp(index as i64)),
+            // days are counted from 2^31, the Unix epoch
+            ColumnType::Date => CqlValue::Date(CqlDate((1u32 << 31).wrapping_add(index as u32))),
+            ColumnType::Time => CqlValue::Time(CqlTime(
+                (index.wrapping_mul(1_000_000_000) % NANOS_PER_DAY) as i64,
+            )),
+            ColumnType::Duration => CqlValue::Duration(CqlDuration {
+                months: 0,
+                days: 0,
+                nanoseconds: (index as i64).wrapping_mul(1_000_000_000),
+            }),
+            ColumnType::Inet => CqlValue::Inet(IpAddr::V4(Ipv4Addr::from(index as u32))),
+            ColumnType::List(element) => CqlValue::List(self.elements(element, rng)),
+            ColumnType::Set(element) => CqlValue::Set(self.elements(element, rng)),
+            ColumnType::Map(key, value) => CqlValue::Map(
+                (0..self.spec.elements.unwrap_or(3))
+                    .map(|_| {
+                        let index = rng.gen::<u32>() as u64;
+                        (self.value(key, index, rng), self.value(value, index, rng))
+                    })
+                    .collect(),
+            ),
+            ColumnType::Tuple(elements) => CqlValue::Tuple(
+                elements
+                    .iter()
+                    .map(|typ| Some(self.value(typ, index, rng)))
+                    .collect(),
+            ),
+            _ => unreachable!("unsupported types are rejected in ValueGenerator::new"),
+        }
+    }
+
+    fn elements(&self, typ: &ColumnType, rng: &mut StdRng) -> Vec<CqlValue> {
+        (0..self.spec.elements.unwrap_or(3))
+            .map(|_| {
+                let index = rng.gen::<u32>() as u64;
+                self.value(typ, index, rng)
+            })
+            .collect()
+    }
+}
+
+/// Random number generator whose output depends only on `seed` and `index`, used to
+/// regenerate the data of any row on demand instead of keeping it in memory
+pub fn index_rng(seed: u64, index: u64) -> StdRng {
+    StdRng::seed_from_u64(splitmix64(seed ^ splitmix64(index)))
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,67 @@
+use crate::populate::{populate, PopulateConfig};
+use crate::signals::Signals;
+use crate::workload::Workload;
+use anyhow::{Context, Result};
+use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
+use std::sync::Arc;
+
+/// Owns the schema and the dataset of a run. The schema is created and the dataset inserted
+/// once, before any executor starts, and the schema is dropped once, after all of them
+/// stopped or the population was interrupted. Executors only prepare their statements and
+/// work on their slice of the dataset
+pub struct SchemaLifecycle {
+    workload: Arc<dyn Workload>,
+    cleanup: bool,
+}
+
+impl SchemaLifecycle {
+    /// Create the schema of the workload. With `cleanup` the schema is dropped by `teardown`
+    pub async fn setup(
+        mut workload: Box<dyn Workload>,
+        session: &GenericSession<CurrentDeserializationApi>,
+        cleanup: bool,
+    ) -> Result<SchemaLifecycle> {
+        println!("Creating the schema...");
+        workload.setup_schema(session).await?;
+        Ok(SchemaLifecycle {
+            workload: Arc::from(workload),
+            cleanup,
+        })
+    }
+
+    /// Insert the initial dataset, see `populate`
+    pub async fn populate(
+        &self,
+        config: &PopulateConfig,
+        session: &Arc<GenericSession<CurrentDeserializationApi>>,
+        signals: &Signals,
+    ) -> Result<()> {
+        println!("Inserting initial key-value pairs...");
+        populate(config, self.workload.clone(), session.clone(), signals).await?;
+        println!("Done inserting initial key-value pairs");
+        Ok(())
+    }
+
+    /// Drop the schema if cleanup is enabled. The data of a failed run, e.g. one that found
+    /// corrupted rows, is kept for inspection
+    pub async fn teardown(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        succeeded: bool,
+    ) -> Result<()> {
+        if !self.cleanup {
+            return Ok(());
+        }
+        if !succeeded {
+            println!("Keeping the schema of the failed run");
+            return Ok(());
+        }
+        println!("Dropping the schema...");
+        self.workload
+            .drop_schema(session)
+            .await
+            .context("Error dropping the schema")?;
+        println!("Dropped the schema");
+        Ok(())
+    }
+}
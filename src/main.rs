@@ -1,212 +1,243 @@
+mod args;
+mod consistency;
+mod distribution;
 mod executor;
+mod generator;
+mod lifecycle;
+mod populate;
+mod profile;
 mod reporter;
-
-use crate::reporter::{PercentileReporter, Reporter};
-use anyhow::Result;
+mod schema;
+mod search;
+mod signals;
+mod trace;
+mod verify;
+mod workload;
+mod write_options;
+
+use crate::args::{Args, Command, RunArgs};
+use crate::distribution::KeySlice;
+use crate::executor::ExecutorConfig;
+use crate::lifecycle::SchemaLifecycle;
+use crate::reporter::{PercentileReporter, Reporter, SimpleReporter};
+use crate::signals::Signals;
+use crate::workload::Workload;
+use anyhow::{bail, Context, Result};
 use clap::Parser;
-use parse_duration::parse;
-use reporter::SimpleReporter;
-use scylla::transport::session::{CurrentDeserializationApi, GenericSession, PoolSize};
-use scylla::SessionBuilder;
-use std::fmt::Debug;
-use std::num::NonZeroUsize;
+use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::Mutex;
-
-#[derive(Parser, Debug)]
-#[command(
-    version,
-    about = "ScyllaDB performance tool",
-    long_about = "A tool for benchmarking Scylla DB performance"
-)]
-struct Args {
-    #[arg(
-        short,
-        long,
-        default_value = "1000",
-        help = "Number of concurrent requests at any given moment of time per executor",
-    )]
-    pub concurrency: usize,
-
-    #[arg(
-        short,
-        long,
-        default_value = "1",
-        help = "Number of executors to run in parallel."
-    )]
-    pub executors_count: usize,
-
-    #[arg(short, long, value_parser = parse, default_value = "10s", help = "Duration of the benchmark"
-    )]
-    pub duration: Duration,
-
-    #[arg(
-        short,
-        long,
-        default_value = "127.0.0.1:9042",
-        help = "Comma-separated list of Scylla hosts with their ports. Example: '127.0.0.1:9042,127.0.0.2:9042,127.0.0.3:9042'"
-    )]
-    pub scylla_hosts: String,
-
-    #[arg(
-        short,
-        long,
-        default_value = "10",
-        help = "Length of the key strings in the database"
-    )]
-    pub key_string_length: usize,
-
-    #[arg(
-        short,
-        long,
-        default_value = "10",
-        help = "Size of the value blobs in the database"
-    )]
-    pub value_blob_size: usize,
-
-    #[arg(
-        short,
-        long,
-        default_value = "0.5",
-        help = "Percentage of reads in the workload. The rest will be writes. Must be between 0.0 and 1.0"
-    )]
-    pub reads_percentage: f32,
-
-    #[arg(
-        short,
-        long,
-        default_value = "1000",
-        help = "Total number of keys in the database"
-    )]
-    pub total_keys: usize,
-
-    #[arg(short = 'u', long, default_value = "cassandra", help = "Scylla user")]
-    pub user: String,
 
-    #[arg(long, default_value = "cassandra", help = "Scylla password")]
-    pub password: String,
-
-    #[arg(
-        short,
-        long,
-        default_value = "2",
-        help = "Number of connections per shard in the connection pool"
-    )]
-    pub pool_size: usize,
-
-    #[arg(
-        short = 'm',
-        long,
-        help = "Available modes: simple, percentile. simple uses less cpu and memory, but provides less information. percentile uses more cpu and memory, but provides more information i.e. 50th, 90th, 99th percentiles",
-        default_value = "simple"
-    )]
-    pub report_mode: String,
-
-    #[arg(
-        long,
-        default_value = "1s",
-        value_parser = parse,
-        help = "Period of reporting results"
-    )]
-    pub report_period: Duration,
-
-    #[arg(
-        long,
-        default_value = "true",
-        help = "Drop the keyspace after the benchmark"
-    )]
-    pub dont_drop_test_keyspace: bool,
-}
-
-fn reporter_mode(mode: String, period: Duration) -> SimpleReporter {
-    let reporter: SimpleReporter = match mode.as_str() {
-        "simple" => SimpleReporter::new(period),
-        // "percentile" => Box::new(PercentileReporter::new(period)),
+fn reporter_mode(mode: String, corrected: bool, warmup: Duration) -> Arc<dyn Reporter> {
+    match mode.as_str() {
+        "simple" => Arc::new(SimpleReporter::new(corrected, warmup)),
+        "percentile" => Arc::new(PercentileReporter::new(corrected, warmup)),
         _ => panic!("Invalid mode: {}", mode),
-    };
-    reporter
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args: Args = Args::parse();
-    let pass_first4 = args.password.chars().take(4).collect::<String>();
-    let pass_after4 = args
-        .password
-        .chars()
-        .skip(4)
-        .map(|_| '*')
-        .collect::<String>();
-    let pass = pass_first4 + &pass_after4;
     println!(
-        "Args: \
-         duration: {}s, scylla_host: {}, pool_size: {},\n\
-         user: {}, password: {}, key_string_length: {},\n\
-         value_blob_size: {}, reads_percentage: {}, total_keys: {},\n\
-         report_mode: {}, report_period: {}s, drop_test_keyspace: {}\
-         executors: {}",
+        "Args: {}, report_period: {}s",
+        args.connection,
+        args.report_period.as_secs_f64()
+    );
+    let session: Arc<GenericSession<CurrentDeserializationApi>> = Arc::new(
+        args.connection
+            .session(args.command.workload().keyspaces_to_fetch())
+            .await?,
+    );
+    match args.command {
+        Command::Populate { workload, populate } => {
+            println!("Workload: {}", workload);
+            let signals = Signals::install()?;
+            let config = populate.config(args.report_period, workload.dataset())?;
+            let mut workload = workload.build(workload.load_profile()?)?;
+            workload.setup_schema(&session).await?;
+            let workload: Arc<dyn Workload> = Arc::from(workload);
+            populate::populate(&config, workload, session, &signals).await?;
+            println!("Done inserting initial key-value pairs");
+        }
+        Command::Run(run_args) => {
+            run(run_args, session, args.report_period, Signals::install()?).await?
+        }
+        Command::Search(search_args) => {
+            search::search(search_args, session, Signals::install()?).await?
+        }
+        Command::Verify {
+            workload,
+            concurrency,
+        } => {
+            println!("Workload: {}", workload);
+            let mut workload = workload.build(workload.load_profile()?)?;
+            if workload.verify_after_run() {
+                bail!("This workload is only verified at the end of 'run', other processes do not know its expected values");
+            }
+            workload.setup_schema(&session).await?;
+            let indexes = 0..workload.population_size();
+            let stats = verify::verify(
+                Arc::from(workload),
+                session,
+                indexes,
+                concurrency,
+                args.report_period,
+            )
+            .await?;
+            if !stats.is_ok() {
+                bail!("Verification failed");
+            }
+            println!("Verification passed");
+        }
+        Command::Cleanup { workload, truncate } => {
+            println!("Workload: {}", workload);
+            let workload = workload.build(workload.load_profile()?)?;
+            if truncate {
+                workload.truncate_schema(&session).await?;
+                println!("Truncated test tables");
+            } else {
+                workload.drop_schema(&session).await?;
+                println!("Dropped the schema");
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn run(
+    args: RunArgs,
+    session: Arc<GenericSession<CurrentDeserializationApi>>,
+    report_period: Duration,
+    signals: Signals,
+) -> Result<()> {
+    println!(
+        "Run: \
+         duration: {}s, warmup: {}s, report_mode: {}, executors: {}, rate: {},\n\
+         populate: {}, cleanup: {}, verify: {}, drain_timeout: {}s,\n\
+         {}",
         args.duration.as_secs_f64(),
-        args.scylla_hosts,
-        args.pool_size,
-        args.user,
-        pass,
-        args.key_string_length,
-        args.value_blob_size,
-        args.reads_percentage,
-        args.total_keys,
+        args.warmup.as_secs_f64(),
         args.report_mode,
-        args.report_period.as_secs_f64(),
-        args.dont_drop_test_keyspace,
-        args.executors_count
+        args.executors_count,
+        args.rate
+            .map(|r| format!("{r} ops/s"))
+            .unwrap_or_else(|| "unlimited".to_string()),
+        args.populate,
+        args.cleanup,
+        args.verify,
+        args.drain_timeout.as_secs_f64(),
+        args.workload
     );
-    let hosts_split = args.scylla_hosts.split(",");
-    let mut builder = SessionBuilder::new()
-        .user(&args.user, &args.password)
-        .pool_size(PoolSize::PerShard(
-            NonZeroUsize::new(args.pool_size).unwrap(),
-        ))
-        .keyspaces_to_fetch(["test"]);
-    for host in hosts_split {
-        builder = builder.known_node(host);
+    let profile = args.workload.load_profile()?;
+    // the warm-up comes on top of the measured duration, a replay ends with its trace
+    let duration = if args.workload.replay.is_some() {
+        Duration::MAX
+    } else {
+        args.warmup
+            + profile
+                .as_ref()
+                .and_then(|p| p.duration())
+                .unwrap_or(args.duration)
+    };
+    // every executor has its own workload, they are built before the schema is created so
+    // that a failure leaves nothing behind
+    let workloads = (0..args.executors_count)
+        .map(|_| args.build_workload(profile.clone()))
+        .collect::<Result<Vec<_>>>()?;
+    let populate_config = args
+        .populate
+        .then(|| {
+            args.populate_args
+                .config(report_period, args.workload.dataset())
+        })
+        .transpose()?;
+    let lifecycle =
+        SchemaLifecycle::setup(args.build_workload(profile)?, &session, args.cleanup).await?;
+    if let Some(config) = populate_config {
+        if let Err(e) = lifecycle.populate(&config, &session, &signals).await {
+            // an interrupted population is cleaned up like a stopped run, a failed one is kept
+            lifecycle.teardown(&session, signals.stopping()).await?;
+            return Err(e);
+        }
     }
-    let session: Arc<GenericSession<CurrentDeserializationApi>> = Arc::new(builder.build().await?);
-    let reporter = Arc::new(reporter_mode(args.report_mode, args.report_period));
+    // latency is measured from the scheduled start of the requests in fixed-rate and replay mode
+    let reporter = reporter_mode(
+        args.report_mode.clone(),
+        args.rate.is_some() || args.workload.replay.is_some(),
+        args.warmup,
+    );
     let reporter_clone_for_thread = reporter.clone();
-    tokio::spawn(async move {
+    let report_signals = signals.clone();
+    let report_loop = tokio::spawn(async move {
         loop {
-            tokio::time::sleep(args.report_period).await;
+            // SIGUSR1 prints a report in between the periodic ones
+            tokio::select! {
+                _ = tokio::time::sleep(report_period) => {}
+                _ = report_signals.report_requested() => {}
+            }
             reporter_clone_for_thread.print_report();
         }
     });
     let mut handles = Vec::new();
-    for i in 0..args.executors_count {
-        let i_clone = i.clone();
+    for (i, workload) in workloads.into_iter().enumerate() {
         let reporter_clone = reporter.clone();
         let session_clone = session.clone();
+        let config = ExecutorConfig {
+            concurrency: args.concurrency,
+            rate: args.rate,
+            slice: KeySlice {
+                index: i,
+                count: args.executors_count,
+            },
+            drain_timeout: Some(args.drain_timeout),
+            stop_on_corruption: args.stop_on_corruption,
+            verify_after_run: true,
+            report_period,
+        };
+        let signals = signals.clone();
         let handle = tokio::spawn(async move {
-            let mut executor = executor::Executor::new(
-                args.concurrency,
-                args.key_string_length,
-                args.value_blob_size,
-                args.reads_percentage,
-                args.total_keys,
-                reporter_clone,
-                args.dont_drop_test_keyspace,
-            );
-            let (stop_sender, executor_thread) = executor.start(session_clone).await.unwrap();
-            tokio::time::sleep(args.duration).await;
-            println!("Requesting stop since the duration has passed");
-            if let Err(e) = stop_sender.send(()) {
-                println!("Error sending stop signal: {:?}", e);
-            }
-            executor_thread.await.unwrap();
-            println!("Executor #{} done", i_clone +1);
+            let executor = executor::Executor::new(config, workload, reporter_clone);
+            let (stop_sender, mut executor_thread) = executor.start(session_clone).await?;
+            // the executor stops early when it finds corrupted data
+            let finished = tokio::select! {
+                _ = tokio::time::sleep(duration) => {
+                    println!("Requesting stop since the duration has passed");
+                    None
+                }
+                _ = signals.stopped() => {
+                    println!("Requesting stop since the benchmark was interrupted");
+                    None
+                }
+                result = &mut executor_thread => Some(result),
+            };
+            let result = match finished {
+                Some(result) => result,
+                None => {
+                    if let Err(e) = stop_sender.send(()) {
+                        println!("Error sending stop signal: {:?}", e);
+                    }
+                    executor_thread.await
+                }
+            };
+            println!("Executor #{} done", i + 1);
+            result.context("Executor coordinator panicked")?
         });
         handles.push(handle);
     }
+    // every executor is joined even if one of them failed, so the report and the teardown run
+    let mut result = Ok(());
     for handle in handles {
-        handle.await?;
+        let executor_result = handle
+            .await
+            .context("Executor panicked")
+            .and_then(|executor_result| executor_result);
+        if result.is_ok() {
+            result = executor_result;
+        }
     }
-    Ok(())
+    report_loop.abort();
+    reporter.print_final_report();
+    lifecycle.teardown(&session, result.is_ok()).await?;
+    result
 }
@@ -0,0 +1,325 @@
+use crate::signals::Signals;
+use crate::workload::Workload;
+use anyhow::{bail, Context, Result};
+use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Keys are handed out to population tasks in chunks of this size, the checkpoint
+/// always points at a chunk boundary
+const CHUNK_SIZE: usize = 1000;
+
+#[derive(Clone, Debug)]
+pub struct PopulateConfig {
+    /// Number of concurrent insert requests
+    pub concurrency: usize,
+    /// Maximum inserts per second, unlimited if `None`
+    pub rate: Option<f64>,
+    /// File storing the number of keys inserted so far, all keys below it are present
+    pub checkpoint: PathBuf,
+    /// Continue from the checkpoint instead of inserting every key
+    pub resume: bool,
+    /// Workload, keyspace, table and seed of the dataset, a checkpoint written for another
+    /// dataset is not resumed
+    pub dataset: String,
+    /// Period of progress reports
+    pub report_period: Duration,
+}
+
+/// Progress shared by the population tasks. Chunks can finish out of order, so the
+/// checkpoint only moves over chunks that have every chunk below them finished.
+struct Progress {
+    next_chunk: AtomicUsize,
+    inserted: AtomicUsize,
+    issued: AtomicU64,
+    failed: AtomicBool,
+    finished_chunks: Mutex<FinishedChunks>,
+}
+
+struct FinishedChunks {
+    /// Every chunk below this one is finished
+    watermark: usize,
+    /// Finished chunks above the watermark
+    pending: BTreeSet<usize>,
+}
+
+impl Progress {
+    fn finish_chunk(&self, chunk: usize) {
+        let mut guard = self.finished_chunks.lock().unwrap();
+        let chunks = &mut *guard;
+        chunks.pending.insert(chunk);
+        while chunks.pending.remove(&chunks.watermark) {
+            chunks.watermark += 1;
+        }
+    }
+
+    fn watermark(&self) -> usize {
+        self.finished_chunks.lock().unwrap().watermark
+    }
+}
+
+fn read_checkpoint(config: &PopulateConfig, total: usize) -> Result<usize> {
+    if !config.resume || !config.checkpoint.exists() {
+        return Ok(0);
+    }
+    let content = std::fs::read_to_string(&config.checkpoint)
+        .with_context(|| format!("Failed to read checkpoint {}", config.checkpoint.display()))?;
+    let (progress, dataset) = content.split_once('\n').unwrap_or((&content, ""));
+    let mut fields = progress.split_whitespace().map(str::parse::<usize>);
+    match (fields.next(), fields.next()) {
+        (Some(Ok(done)), Some(Ok(checkpoint_total))) => {
+            let dataset = dataset.trim();
+            if dataset != config.dataset {
+                bail!(
+                    "Checkpoint {} was written for another dataset ({}), the configured one is {}",
+                    config.checkpoint.display(),
+                    if dataset.is_empty() {
+                        "unknown"
+                    } else {
+                        dataset
+                    },
+                    config.dataset
+                );
+            }
+            if checkpoint_total != total {
+                bail!(
+                    "Checkpoint {} was written for {} keys, but {} keys are configured",
+                    config.checkpoint.display(),
+                    checkpoint_total,
+                    total
+                );
+            }
+            Ok(done.min(total))
+        }
+        _ => bail!("Malformed checkpoint {}", config.checkpoint.display()),
+    }
+}
+
+fn write_checkpoint(config: &PopulateConfig, done: usize, total: usize) -> Result<()> {
+    // write to a temporary file first, so a crash never leaves a truncated checkpoint
+    let tmp = config.checkpoint.with_extension("tmp");
+    std::fs::write(&tmp, format!("{} {}\n{}\n", done, total, config.dataset))
+        .and_then(|_| std::fs::rename(&tmp, &config.checkpoint))
+        .with_context(|| format!("Failed to write checkpoint {}", config.checkpoint.display()))
+}
+
+fn print_progress(inserted: usize, start_from: usize, total: usize, started_at: Instant) {
+    let elapsed = started_at.elapsed().as_secs_f64();
+    let throughput = (inserted - start_from) as f64 / elapsed;
+    let remaining = total - inserted;
+    let eta = if throughput > 0.0 {
+        format!("{:.0}s", remaining as f64 / throughput)
+    } else {
+        "unknown".to_string()
+    };
+    println!(
+        "Populated {}/{} keys ({:.1}%), {:.2} keys/s, ETA: {}",
+        inserted,
+        total,
+        inserted as f64 * 100.0 / total.max(1) as f64,
+        throughput,
+        eta
+    );
+}
+
+/// Insert rows `0..workload.population_size()`, skipping the ones recorded in the
+/// checkpoint when resuming. A stop signal ends the population with an error once the
+/// inserts in flight are done and the checkpoint is written
+pub async fn populate(
+    config: &PopulateConfig,
+    workload: Arc<dyn Workload>,
+    session: Arc<GenericSession<CurrentDeserializationApi>>,
+    signals: &Signals,
+) -> Result<()> {
+    let total = workload.population_size();
+    let start_from = read_checkpoint(config, total)?;
+    if start_from >= total && total > 0 {
+        println!(
+            "Checkpoint shows all {} keys inserted, skipping population",
+            total
+        );
+        return Ok(());
+    }
+    if start_from > 0 {
+        println!("Resuming population from key {}", start_from);
+    }
+    let first_chunk = start_from / CHUNK_SIZE;
+    let progress = Arc::new(Progress {
+        next_chunk: AtomicUsize::new(first_chunk),
+        inserted: AtomicUsize::new(start_from),
+        issued: AtomicU64::new(0),
+        failed: AtomicBool::new(false),
+        finished_chunks: Mutex::new(FinishedChunks {
+            watermark: first_chunk,
+            pending: BTreeSet::new(),
+        }),
+    });
+    let started_at = Instant::now();
+    let mut workers = Vec::new();
+    for _ in 0..config.concurrency.max(1) {
+        let progress = progress.clone();
+        let workload = workload.clone();
+        let session = session.clone();
+        let signals = signals.clone();
+        let rate = config.rate;
+        workers.push(tokio::spawn(async move {
+            loop {
+                let chunk = progress.next_chunk.fetch_add(1, Ordering::Relaxed);
+                let range = chunk * CHUNK_SIZE..((chunk + 1) * CHUNK_SIZE).min(total);
+                if range.is_empty() || progress.failed.load(Ordering::Relaxed) {
+                    return Ok(());
+                }
+                for index in range {
+                    // the unfinished chunk stays above the watermark and is inserted again
+                    // when resuming
+                    if signals.stopping() {
+                        return Ok(());
+                    }
+                    if let Some(rate) = rate {
+                        let slot = progress.issued.fetch_add(1, Ordering::Relaxed);
+                        let at = started_at + Duration::from_secs_f64(slot as f64 / rate);
+                        tokio::time::sleep_until(at).await;
+                    }
+                    if let Err(e) = workload.populate(&session, index).await {
+                        progress.failed.store(true, Ordering::Relaxed);
+                        return Err(e.context(format!("Failed to insert key {}", index)));
+                    }
+                    progress.inserted.fetch_add(1, Ordering::Relaxed);
+                }
+                progress.finish_chunk(chunk);
+            }
+        }));
+    }
+    let reporter_progress = progress.clone();
+    let report_config = config.clone();
+    let reporter = tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(report_config.report_period).await;
+            let inserted = reporter_progress.inserted.load(Ordering::Relaxed);
+            print_progress(inserted, start_from, total, started_at);
+            let done = (reporter_progress.watermark() * CHUNK_SIZE).min(total);
+            if let Err(e) = write_checkpoint(&report_config, done, total) {
+                println!("{:?}", e);
+            }
+        }
+    });
+    let mut result = Ok(());
+    for worker in workers {
+        let worker_result = worker.await.context("Population task panicked")?;
+        if result.is_ok() {
+            result = worker_result;
+        }
+    }
+    reporter.abort();
+    let done = (progress.watermark() * CHUNK_SIZE).min(total);
+    write_checkpoint(config, done, total)?;
+    print_progress(
+        progress.inserted.load(Ordering::Relaxed),
+        start_from,
+        total,
+        started_at,
+    );
+    if result.is_ok() && signals.stopping() {
+        println!(
+            "Wrote checkpoint {} with {} keys",
+            config.checkpoint.display(),
+            done
+        );
+        bail!("Population interrupted, use --resume to continue");
+    }
+    result.with_context(|| {
+        format!(
+            "Population stopped, {} keys are checkpointed in {}, use --resume to continue",
+            done,
+            config.checkpoint.display()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::Args;
+    use clap::Parser;
+
+    fn progress(first_chunk: usize) -> Progress {
+        Progress {
+            next_chunk: AtomicUsize::new(first_chunk),
+            inserted: AtomicUsize::new(0),
+            issued: AtomicU64::new(0),
+            failed: AtomicBool::new(false),
+            finished_chunks: Mutex::new(FinishedChunks {
+                watermark: first_chunk,
+                pending: BTreeSet::new(),
+            }),
+        }
+    }
+
+    fn config(checkpoint: PathBuf, dataset: &str) -> PopulateConfig {
+        PopulateConfig {
+            concurrency: 1,
+            rate: None,
+            checkpoint,
+            resume: true,
+            dataset: dataset.to_string(),
+            report_period: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn watermark_waits_for_chunks_finished_out_of_order() {
+        let progress = progress(0);
+        progress.finish_chunk(1);
+        progress.finish_chunk(3);
+        assert_eq!(progress.watermark(), 0);
+        progress.finish_chunk(0);
+        assert_eq!(progress.watermark(), 2);
+        progress.finish_chunk(2);
+        assert_eq!(progress.watermark(), 4);
+    }
+
+    #[test]
+    fn watermark_starts_at_the_resumed_chunk() {
+        let progress = progress(5);
+        progress.finish_chunk(6);
+        assert_eq!(progress.watermark(), 5);
+        progress.finish_chunk(5);
+        assert_eq!(progress.watermark(), 7);
+    }
+
+    #[test]
+    fn checkpoint_is_only_resumed_for_the_same_dataset() {
+        let path = std::env::temp_dir().join(format!(
+            "scylla-perf-test-{}.checkpoint",
+            std::process::id()
+        ));
+        let written = config(path.clone(), "workload: key-value, seed: 1");
+        write_checkpoint(&written, 3000, 10000).unwrap();
+        assert_eq!(read_checkpoint(&written, 10000).unwrap(), 3000);
+        assert!(read_checkpoint(&written, 20000).is_err());
+        let other = config(path.clone(), "workload: key-value, seed: 2");
+        assert!(read_checkpoint(&other, 10000).is_err());
+        let dataset = |args: &[&str]| {
+            let args = ["scylla-perf", "populate", "-w", "wide"].iter().chain(args);
+            Args::try_parse_from(args)
+                .unwrap()
+                .command
+                .workload()
+                .dataset()
+        };
+        let written = config(path.clone(), &dataset(&["--rows-per-partition", "100"]));
+        write_checkpoint(&written, 3000, 10000).unwrap();
+        let other = config(path.clone(), &dataset(&["--rows-per-partition", "50"]));
+        assert!(read_checkpoint(&other, 10000).is_err());
+        let other = config(path.clone(), &dataset(&["--value-blob-size", "20"]));
+        assert!(read_checkpoint(&other, 10000).is_err());
+        let same = config(path.clone(), &dataset(&["--rows-per-partition", "100"]));
+        assert_eq!(read_checkpoint(&same, 10000).unwrap(), 3000);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read_checkpoint(&written, 10000).unwrap(), 0);
+    }
+}
@@ -0,0 +1,245 @@
+use crate::consistency::{ConsistencyLevel, SerialConsistencyLevel};
+use crate::distribution::KeyDistribution;
+use crate::generator::GeneratorSpec;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Benchmark description loaded from a TOML or YAML file with `--profile`.
+///
+/// Example:
+/// ```toml
+/// [schema]
+/// keyspace = "CREATE KEYSPACE IF NOT EXISTS perf WITH REPLICATION = { 'class' : 'SimpleStrategy', 'replication_factor' : 1 }"
+/// tables = ["CREATE TABLE IF NOT EXISTS perf.events (id bigint PRIMARY KEY, payload blob)"]
+/// drop = ["DROP KEYSPACE IF EXISTS perf"]
+/// truncate = ["TRUNCATE perf.events"]
+///
+/// [population]
+/// statement = "insert"
+/// rows = 100000
+///
+/// [statements.insert]
+/// cql = "INSERT INTO perf.events (id, payload) VALUES (?, ?)"
+/// weight = 1
+///
+/// [statements.select]
+/// cql = "SELECT * FROM perf.events WHERE id = ?"
+/// weight = 4
+/// key_distribution = "zipfian:0.99"
+/// consistency = "LOCAL_ONE"
+///
+/// [generators.payload]
+/// size = 256
+///
+/// [[phases]]
+/// name = "write-heavy"
+/// duration = "30s"
+/// weights = { insert = 4, select = 1 }
+///
+/// [[phases]]
+/// name = "read-heavy"
+/// duration = "1m"
+/// ```
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub schema: SchemaSpec,
+    pub population: Option<PopulationSpec>,
+    pub statements: BTreeMap<String, StatementSpec>,
+    /// Generators of bind variables, keyed by the bind-variable (column) name.
+    /// Columns without an entry use the defaults of their CQL type
+    #[serde(default)]
+    pub generators: BTreeMap<String, GeneratorSpec>,
+    #[serde(default)]
+    pub phases: Vec<PhaseSpec>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SchemaSpec {
+    /// `CREATE KEYSPACE` statement
+    pub keyspace: Option<String>,
+    /// `CREATE TABLE` (or `CREATE TYPE`, `CREATE INDEX`...) statements, run in order
+    #[serde(default)]
+    pub tables: Vec<String>,
+    /// Statements run when the schema is dropped, by `cleanup` or `run --cleanup`
+    #[serde(default)]
+    pub drop: Vec<String>,
+    /// Statements run by `cleanup --truncate`
+    #[serde(default)]
+    pub truncate: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PopulationSpec {
+    /// Name of the statement used to insert the initial rows
+    pub statement: String,
+    /// Number of rows to insert, benchmark queries pick row indexes from `0..rows`
+    pub rows: usize,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct StatementSpec {
+    pub cql: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    /// How the statement picks row indexes, defaults to `--key-distribution`
+    pub key_distribution: Option<KeyDistribution>,
+    /// Defaults to `--read-consistency` for `SELECT` statements, `--write-consistency` otherwise
+    pub consistency: Option<ConsistencyLevel>,
+    /// Defaults to `--serial-consistency`
+    pub serial_consistency: Option<SerialConsistencyLevel>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PhaseSpec {
+    pub name: String,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub duration: Duration,
+    /// Statement weights used during this phase instead of the statement defaults.
+    /// Statements not listed here are not run in this phase
+    pub weights: Option<BTreeMap<String, u32>>,
+}
+
+/// Statement given on the command line with `--query "[<weight>:]<cql>"`, e.g.
+/// `--query "4:SELECT * FROM ks.t WHERE id = ? AND ts > ?"`. The weight defaults to 1
+#[derive(Clone, Debug)]
+pub struct QuerySpec {
+    pub weight: u32,
+    pub cql: String,
+}
+
+impl FromStr for QuerySpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (weight, cql) = match s.split_once(':') {
+            Some((weight, cql))
+                if !weight.is_empty() && weight.chars().all(|c| c.is_ascii_digit()) =>
+            {
+                (weight.parse()?, cql)
+            }
+            _ => (default_weight(), s),
+        };
+        if cql.trim().is_empty() {
+            bail!("Query '{}' has no CQL statement", s);
+        }
+        Ok(QuerySpec {
+            weight,
+            cql: cql.trim().to_string(),
+        })
+    }
+}
+
+impl fmt::Display for QuerySpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.weight, self.cql)
+    }
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn deserialize_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_duration::parse(&s).map_err(serde::de::Error::custom)
+}
+
+impl Profile {
+    /// Load a profile, `.yaml` and `.yml` files are parsed as YAML, anything else as TOML
+    pub fn load(path: &Path) -> Result<Profile> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read profile {}", path.display()))?;
+        let profile: Profile = match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&content)
+                .with_context(|| format!("Failed to parse YAML profile {}", path.display()))?,
+            _ => toml::from_str(&content)
+                .with_context(|| format!("Failed to parse TOML profile {}", path.display()))?,
+        };
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    /// Profile of the statements given with `--query`, named `query1`, `query2`... in order.
+    /// It has no schema, the statements run against existing tables
+    pub fn from_queries(queries: &[QuerySpec]) -> Result<Profile> {
+        let profile = Profile {
+            schema: SchemaSpec::default(),
+            population: None,
+            statements: queries
+                .iter()
+                .enumerate()
+                .map(|(i, query)| {
+                    let statement = StatementSpec {
+                        cql: query.cql.clone(),
+                        weight: query.weight,
+                        key_distribution: None,
+                        consistency: None,
+                        serial_consistency: None,
+                    };
+                    (format!("query{}", i + 1), statement)
+                })
+                .collect(),
+            generators: BTreeMap::new(),
+            phases: Vec::new(),
+        };
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.statements.is_empty() {
+            bail!("Profile must define at least one statement");
+        }
+        if let Some(population) = &self.population {
+            if !self.statements.contains_key(&population.statement) {
+                bail!(
+                    "Population statement '{}' is not defined",
+                    population.statement
+                );
+            }
+        }
+        if self.statements.values().all(|s| s.weight == 0) && self.phases.is_empty() {
+            bail!("At least one statement must have a positive weight");
+        }
+        for phase in &self.phases {
+            if let Some(weights) = &phase.weights {
+                if let Some(name) = weights.keys().find(|n| !self.statements.contains_key(*n)) {
+                    bail!(
+                        "Phase '{}' refers to unknown statement '{}'",
+                        phase.name,
+                        name
+                    );
+                }
+                if weights.values().all(|w| *w == 0) {
+                    bail!(
+                        "Phase '{}' has no statement with a positive weight",
+                        phase.name
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Total duration of all phases, `None` if the profile has no phases
+    pub fn duration(&self) -> Option<Duration> {
+        if self.phases.is_empty() {
+            None
+        } else {
+            Some(self.phases.iter().map(|p| p.duration).sum())
+        }
+    }
+}
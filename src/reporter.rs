@@ -1,23 +1,83 @@
+use crate::consistency::{ConsistencyLevel, CONSISTENCY_LEVELS};
+use crate::workload::{TombstoneAlert, Verification};
 use comfy_table::presets::UTF8_FULL;
 use comfy_table::{Cell, Color, ContentArrangement, Table};
-use histogram::Histogram;
+use histogram::{AtomicHistogram, Histogram};
 use human_format::Formatter;
 use std::collections::BTreeMap;
 use std::fmt;
-use std::sync::atomic::AtomicUsize;
-use std::sync::Arc;
-use tokio::spawn;
-use tokio::sync::Mutex;
-use tokio::time::Instant;
-use tokio_schedule::{every, Job};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
 use std::time::Duration;
+use tokio::time::Instant;
 
-pub trait Reporter {
-    /// Create a new reporter with a given period between reports
-    fn new(period: Duration) -> Self
+pub trait Reporter: Send + Sync {
+    /// Create a new reporter. When `corrected` is set, the reporter also shows latencies
+    /// corrected for coordinated omission, i.e. measured from the intended start time.
+    /// Results of the first `warmup` after the first reported result are kept apart and
+    /// left out of the statistics
+    fn new(corrected: bool, warmup: Duration) -> Self
     where
         Self: Sized;
-    fn report_results(&self, query_type: QueryType, time: Duration) -> ();
+    /// Record a finished operation. `latency` is measured from the moment the query was sent,
+    /// `corrected_latency` from the moment it was scheduled to be sent
+    fn report_results(&self, operation: &Operation, latency: Duration, corrected_latency: Duration);
+    /// Print the statistics collected so far, called periodically during the run
+    fn print_report(&self);
+    /// Print the statistics of the whole run, with the warm-up shown separately
+    fn print_final_report(&self);
+    /// End the run now: requests still in flight are recorded, but the time spent draining
+    /// them and verifying the dataset does not count into the rates. Later calls keep the
+    /// first end
+    fn stop(&self);
+}
+
+/// Splits a run into the warm-up and the measured part. Time is counted from the first
+/// reported result, so schema setup and population do not eat into the warm-up, until
+/// the run is stopped
+struct Clock {
+    warmup: Duration,
+    started_at: OnceLock<Instant>,
+    stopped_at: OnceLock<Instant>,
+}
+
+impl Clock {
+    fn new(warmup: Duration) -> Clock {
+        Clock {
+            warmup,
+            started_at: OnceLock::new(),
+            stopped_at: OnceLock::new(),
+        }
+    }
+
+    fn stop(&self) {
+        self.stopped_at.get_or_init(Instant::now);
+    }
+
+    /// Time since the first result, up to the stop of the run
+    fn elapsed(&self, started_at: Instant) -> Duration {
+        let now = self.stopped_at.get().copied().unwrap_or_else(Instant::now);
+        now.saturating_duration_since(started_at)
+    }
+
+    /// Whether a result reported now belongs to the warm-up
+    fn is_warmup(&self) -> bool {
+        self.elapsed(*self.started_at.get_or_init(Instant::now)) < self.warmup
+    }
+
+    /// Seconds spent in the warm-up so far, `None` before the first result
+    fn warmup_elapsed(&self) -> Option<f64> {
+        let started_at = self.started_at.get()?;
+        Some(self.elapsed(*started_at).min(self.warmup).as_secs_f64())
+    }
+
+    /// Seconds since the end of the warm-up, `None` while still warming up
+    fn measured_elapsed(&self) -> Option<f64> {
+        let started_at = self.started_at.get()?;
+        self.elapsed(*started_at)
+            .checked_sub(self.warmup)
+            .map(|elapsed| elapsed.as_secs_f64())
+    }
 }
 
 #[derive(Hash, Eq, PartialEq, Copy, Clone, Debug, Ord, PartialOrd)]
@@ -25,139 +85,858 @@ pub enum QueryType {
     Total,
     Read,
     Write,
+    /// `INSERT ... IF NOT EXISTS`
+    CasInsert,
+    /// `UPDATE ... IF <condition>`
+    CasUpdate,
+    /// Batch of writes, see `Operation::rows`
+    Batch,
+    /// Read of `LIMIT` rows of a partition in clustering order
+    SliceRead,
+    /// Read of `LIMIT` rows of a partition in reverse clustering order
+    ReverseRead,
+    /// Paged read of a whole partition
+    PartitionRead,
+    /// Delete of a single clustering row
+    RowDelete,
+    /// Delete of a range of clustering rows
+    RangeDelete,
+    /// Delete of the value cell of a single row
+    CellDelete,
+    /// Element added to a collection
+    Append,
+    /// Element removed from a collection, or field of a user-defined type column cleared
+    Remove,
+    /// Field of a user-defined type column set
+    FieldUpdate,
+    /// Whole collection replaced
+    Overwrite,
+    /// Counter update with a positive delta
+    Increment,
+    /// Counter update with a negative delta
+    Decrement,
+    /// Read of the newest points of a time series
+    LatestRead,
+    /// Read of the points of a time series within a range of time
+    RangeRead,
+    /// Named statement of a workload profile
+    Custom(&'static str),
+}
+
+/// What a workload tells the reporter about a finished operation
+#[derive(Clone, Debug)]
+pub struct Operation {
+    pub query_type: QueryType,
+    /// Size in bytes of the value read or written, if the operation knows it
+    pub value_size: Option<usize>,
+    /// Outcome of comparing the row read with the expected value, if it was checked
+    pub verification: Option<Verification>,
+    /// Consistency level the statement ran at, if it was set explicitly
+    pub consistency: Option<ConsistencyLevel>,
+    /// Number of rows written by a batch or returned by a multi-row read
+    pub rows: Option<usize>,
+    /// Whether the condition of a lightweight transaction held
+    pub applied: Option<bool>,
+    /// The operation timed out, its latency is not recorded
+    pub timed_out: bool,
+    /// Tombstones the workload wrote to the partition before reading it, if it tracks them
+    pub tombstones: Option<usize>,
+    /// Tombstone warning or failure of a read, the latency of failed reads is not recorded
+    pub tombstone_alert: Option<TombstoneAlert>,
+}
+
+impl Operation {
+    pub fn new(query_type: QueryType) -> Operation {
+        Operation {
+            query_type,
+            value_size: None,
+            verification: None,
+            consistency: None,
+            rows: None,
+            applied: None,
+            timed_out: false,
+            tombstones: None,
+            tombstone_alert: None,
+        }
+    }
+
+    pub fn with_value_size(mut self, value_size: usize) -> Operation {
+        self.value_size = Some(value_size);
+        self
+    }
+
+    pub fn with_consistency(mut self, consistency: Option<ConsistencyLevel>) -> Operation {
+        self.consistency = consistency;
+        self
+    }
+
+    pub fn with_rows(mut self, rows: usize) -> Operation {
+        self.rows = Some(rows);
+        self
+    }
+
+    pub fn with_applied(mut self, applied: bool) -> Operation {
+        self.applied = Some(applied);
+        self
+    }
+
+    pub fn with_timeout(mut self) -> Operation {
+        self.timed_out = true;
+        self
+    }
+
+    pub fn with_verification(mut self, verification: Verification) -> Operation {
+        self.verification = Some(verification);
+        self
+    }
+
+    pub fn with_tombstones(mut self, tombstones: usize) -> Operation {
+        self.tombstones = Some(tombstones);
+        self
+    }
+
+    pub fn with_tombstone_alert(mut self, alert: Option<TombstoneAlert>) -> Operation {
+        self.tombstone_alert = alert;
+        self
+    }
+
+    /// Timed out and failed operations only count as outcomes, they have no latency
+    fn has_latency(&self) -> bool {
+        !self.timed_out && self.tombstone_alert != Some(TombstoneAlert::Failure)
+    }
+}
+
+/// Outcomes of lightweight transactions
+#[derive(Default)]
+struct LwtCounts {
+    applied: AtomicUsize,
+    not_applied: AtomicUsize,
+    timeouts: AtomicUsize,
+}
+
+impl LwtCounts {
+    fn record(&self, operation: &Operation) {
+        let counter = match (operation.timed_out, operation.applied) {
+            (true, _) => &self.timeouts,
+            (false, Some(true)) => &self.applied,
+            (false, Some(false)) => &self.not_applied,
+            (false, None) => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Applied, not applied and timed out operations
+    fn load(&self) -> (usize, usize, usize) {
+        (
+            self.applied.load(Ordering::Relaxed),
+            self.not_applied.load(Ordering::Relaxed),
+            self.timeouts.load(Ordering::Relaxed),
+        )
+    }
+
+    fn applied_percentage(applied: usize, not_applied: usize) -> f64 {
+        applied as f64 * 100.0 / (applied + not_applied).max(1) as f64
+    }
+
+    fn print(&self) {
+        let (applied, not_applied, timeouts) = self.load();
+        if applied + not_applied + timeouts > 0 {
+            println!(
+                "LWT applied: {}, not applied: {} ({:.1}% applied), timeouts: {}",
+                applied,
+                not_applied,
+                Self::applied_percentage(applied, not_applied),
+                timeouts
+            );
+        }
+    }
+}
+
+/// Reads that ran into the tombstone warning or failure threshold
+#[derive(Default)]
+struct TombstoneAlerts {
+    warnings: AtomicUsize,
+    failures: AtomicUsize,
+}
+
+impl TombstoneAlerts {
+    fn record(&self, alert: Option<TombstoneAlert>) {
+        let counter = match alert {
+            Some(TombstoneAlert::Warning) => &self.warnings,
+            Some(TombstoneAlert::Failure) => &self.failures,
+            None => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn print(&self) {
+        let warnings = self.warnings.load(Ordering::Relaxed);
+        let failures = self.failures.load(Ordering::Relaxed);
+        if warnings + failures > 0 {
+            println!(
+                "Tombstone warnings: {}, reads failed on tombstones: {}",
+                warnings, failures
+            );
+        }
+    }
+}
+
+/// Outcomes of reads compared with the expected value
+#[derive(Default)]
+struct VerificationCounts {
+    verified: AtomicUsize,
+    missing: AtomicUsize,
+    mismatched: AtomicUsize,
+}
+
+impl VerificationCounts {
+    fn record(&self, verification: Option<Verification>) {
+        let counter = match verification {
+            Some(Verification::Ok) => &self.verified,
+            Some(Verification::Missing) => &self.missing,
+            Some(Verification::Mismatch) => &self.mismatched,
+            None => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn print(&self) {
+        let missing = self.missing.load(Ordering::Relaxed);
+        let mismatched = self.mismatched.load(Ordering::Relaxed);
+        let checked = self.verified.load(Ordering::Relaxed) + missing + mismatched;
+        if checked > 0 {
+            println!(
+                "Verified reads: {}, missing rows: {}, mismatched values: {}",
+                checked, missing, mismatched
+            );
+        }
+    }
+}
+
+/// Upper bounds of the value size buckets of the latency breakdown, the last bucket
+/// holds everything larger
+const SIZE_BUCKETS: [usize; 8] = [64, 256, 1024, 4096, 16384, 65536, 262144, 1048576];
+
+fn size_bucket(size: usize) -> usize {
+    SIZE_BUCKETS
+        .iter()
+        .position(|bound| size <= *bound)
+        .unwrap_or(SIZE_BUCKETS.len())
+}
+
+fn size_bucket_label(bucket: usize) -> String {
+    fn format_size(bytes: usize) -> String {
+        match bytes {
+            b if b >= 1 << 20 => format!("{} MiB", b >> 20),
+            b if b >= 1 << 10 => format!("{} KiB", b >> 10),
+            b => format!("{} B", b),
+        }
+    }
+    match SIZE_BUCKETS.get(bucket) {
+        Some(bound) => format!("<= {}", format_size(*bound)),
+        None => format!("> {}", format_size(SIZE_BUCKETS[SIZE_BUCKETS.len() - 1])),
+    }
+}
+
+/// Upper bounds of the buckets of tombstones in the partition read, the last bucket
+/// holds everything larger
+const TOMBSTONE_BUCKETS: [usize; 5] = [0, 10, 100, 1000, 10000];
+
+fn tombstone_bucket(tombstones: usize) -> usize {
+    TOMBSTONE_BUCKETS
+        .iter()
+        .position(|bound| tombstones <= *bound)
+        .unwrap_or(TOMBSTONE_BUCKETS.len())
+}
+
+fn tombstone_bucket_label(bucket: usize) -> String {
+    match (bucket.checked_sub(1), TOMBSTONE_BUCKETS.get(bucket)) {
+        (None, _) => "0".to_string(),
+        (Some(previous), Some(bound)) => format!("{}-{}", TOMBSTONE_BUCKETS[previous] + 1, bound),
+        (Some(_), None) => format!("> {}", TOMBSTONE_BUCKETS[TOMBSTONE_BUCKETS.len() - 1]),
+    }
 }
 
 pub struct SimpleReporter {
-    period: Duration,
+    corrected: bool,
+    clock: Clock,
+    warmup: SimpleStats,
+    measured: SimpleStats,
+}
+
+#[derive(Default)]
+struct SimpleStats {
     request_counts: AtomicUsize,
     request_durations_micros: AtomicUsize,
-    first_reported_at: Instant,
-    last_reported_at: Instant,
+    corrected_durations_micros: AtomicUsize,
+    size_counts: [AtomicUsize; SIZE_BUCKETS.len() + 1],
+    size_durations_micros: [AtomicUsize; SIZE_BUCKETS.len() + 1],
+    consistency_counts: [AtomicUsize; CONSISTENCY_LEVELS],
+    consistency_durations_micros: [AtomicUsize; CONSISTENCY_LEVELS],
+    row_counts: AtomicUsize,
+    row_durations_micros: AtomicUsize,
+    tombstone_counts: [AtomicUsize; TOMBSTONE_BUCKETS.len() + 1],
+    tombstone_durations_micros: [AtomicUsize; TOMBSTONE_BUCKETS.len() + 1],
+    tombstone_alerts: TombstoneAlerts,
+    lwt: LwtCounts,
+    verification: VerificationCounts,
+    /// Only kept in corrected mode, where the averages alone hide the tail the correction adds
+    histograms: Option<CorrectedHistograms>,
+}
+
+/// Uncorrected and corrected latency of all requests
+struct CorrectedHistograms {
+    latency: AtomicHistogram,
+    corrected: AtomicHistogram,
+}
+
+impl CorrectedHistograms {
+    fn new() -> CorrectedHistograms {
+        CorrectedHistograms {
+            latency: AtomicHistogram::new(7, 64).unwrap(),
+            corrected: AtomicHistogram::new(7, 64).unwrap(),
+        }
+    }
+
+    fn record(&self, latency: Duration, corrected_latency: Duration) {
+        let latency = self.latency.increment(latency.as_micros() as u64);
+        let corrected = self
+            .corrected
+            .increment(corrected_latency.as_micros() as u64);
+        if latency.is_err() || corrected.is_err() {
+            println!("Failed to add latency to histogram");
+        }
+    }
+
+    fn print(&self) {
+        let percentiles = |hist: &Histogram| {
+            [50.0, 75.0, 95.0, 99.0]
+                .iter()
+                .map(|p| {
+                    let value = match hist.percentile(*p) {
+                        Ok(Some(bucket)) => format!(
+                            "{:.2} ms",
+                            (bucket.start() + bucket.end()) as f64 / 2.0 / 1000.0
+                        ),
+                        _ => "-".to_string(),
+                    };
+                    format!("p{}: {}", p, value)
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        println!("  Latency {}", percentiles(&self.latency.load()));
+        println!(
+            "  Corrected latency {}",
+            percentiles(&self.corrected.load())
+        );
+    }
 }
 
-#[derive(Clone)]
 pub struct PercentileReporter {
-    period: Duration,
+    corrected: bool,
+    clock: Clock,
+    warmup: Mutex<PercentileState>,
+    measured: Mutex<PercentileState>,
+}
+
+#[derive(Default)]
+struct PercentileState {
     request_counts: BTreeMap<QueryType, usize>,
     request_durations: BTreeMap<QueryType, Histogram>,
-    first_reported_at: Instant,
-    last_reported_at: Instant,
-}
-
-unsafe impl Send for SimpleReporter {}
-unsafe impl Send for PercentileReporter {}
-unsafe impl Sync for SimpleReporter {}
-
-impl SimpleReporter {
-    pub fn new(period: Duration) -> Self {
-        let res = SimpleReporter {
-            period,
-            request_counts: AtomicUsize::new(0),
-            request_durations_micros: AtomicUsize::new(0),
-            last_reported_at: Instant::now(),
-            first_reported_at: Instant::now(),
-        };
-        res
+    corrected_durations: BTreeMap<QueryType, Histogram>,
+    size_counts: BTreeMap<usize, usize>,
+    size_durations: BTreeMap<usize, Histogram>,
+    consistency_counts: BTreeMap<ConsistencyLevel, usize>,
+    consistency_durations: BTreeMap<ConsistencyLevel, Histogram>,
+    /// Rows of batches and multi-row reads and their latency divided by the number of rows
+    row_counts: BTreeMap<QueryType, usize>,
+    row_durations: BTreeMap<QueryType, Histogram>,
+    /// Reads by the bucket of tombstones written to the partition read
+    tombstone_counts: BTreeMap<usize, usize>,
+    tombstone_durations: BTreeMap<usize, Histogram>,
+    tombstone_alerts: TombstoneAlerts,
+    lwt: BTreeMap<QueryType, LwtCounts>,
+    verification: VerificationCounts,
+}
+
+impl SimpleStats {
+    fn new(corrected: bool) -> SimpleStats {
+        SimpleStats {
+            histograms: corrected.then(CorrectedHistograms::new),
+            ..SimpleStats::default()
+        }
     }
 
-    pub fn print_report(&self) {
-        let request_counts = self.request_counts.load(std::sync::atomic::Ordering::Relaxed);
-        let request_durations_micros = self.request_durations_micros.load(std::sync::atomic::Ordering::Relaxed);
-        let rps = request_counts as f64 / self.first_reported_at.elapsed().as_secs_f64();
+    fn record(&self, operation: &Operation, latency: Duration, corrected_latency: Duration) {
+        self.lwt.record(operation);
+        self.tombstone_alerts.record(operation.tombstone_alert);
+        if !operation.has_latency() {
+            return;
+        }
+        self.request_counts.fetch_add(1, Ordering::Relaxed);
+        self.request_durations_micros
+            .fetch_add(latency.as_micros() as usize, Ordering::Relaxed);
+        self.corrected_durations_micros
+            .fetch_add(corrected_latency.as_micros() as usize, Ordering::Relaxed);
+        if let Some(histograms) = &self.histograms {
+            histograms.record(latency, corrected_latency);
+        }
+        if let Some(size) = operation.value_size {
+            let bucket = size_bucket(size);
+            self.size_counts[bucket].fetch_add(1, Ordering::Relaxed);
+            self.size_durations_micros[bucket]
+                .fetch_add(latency.as_micros() as usize, Ordering::Relaxed);
+        }
+        if let Some(consistency) = operation.consistency {
+            self.consistency_counts[consistency.index()].fetch_add(1, Ordering::Relaxed);
+            self.consistency_durations_micros[consistency.index()]
+                .fetch_add(latency.as_micros() as usize, Ordering::Relaxed);
+        }
+        if let Some(rows) = operation.rows {
+            self.row_counts.fetch_add(rows, Ordering::Relaxed);
+            self.row_durations_micros
+                .fetch_add(latency.as_micros() as usize, Ordering::Relaxed);
+        }
+        if let Some(tombstones) = operation.tombstones {
+            let bucket = tombstone_bucket(tombstones);
+            self.tombstone_counts[bucket].fetch_add(1, Ordering::Relaxed);
+            self.tombstone_durations_micros[bucket]
+                .fetch_add(latency.as_micros() as usize, Ordering::Relaxed);
+        }
+        self.verification.record(operation.verification);
+    }
+
+    fn print(&self, corrected: bool, elapsed: f64) {
+        let request_counts = self.request_counts.load(Ordering::Relaxed);
+        let request_durations_micros = self.request_durations_micros.load(Ordering::Relaxed);
+        let rps = request_counts as f64 / elapsed;
         let avg_latency = request_durations_micros as f64 / request_counts as f64;
-        println!(
-            "Total requests: {}, RPS: {:.2}, Avg latency: {:.2} ms",
-            request_counts, rps, avg_latency / 1000.0
-        );
+        if corrected {
+            let corrected_durations_micros =
+                self.corrected_durations_micros.load(Ordering::Relaxed);
+            let avg_corrected = corrected_durations_micros as f64 / request_counts as f64;
+            println!(
+                "Total requests: {}, RPS: {:.2}, Avg latency: {:.2} ms, Avg corrected latency: {:.2} ms",
+                request_counts,
+                rps,
+                avg_latency / 1000.0,
+                avg_corrected / 1000.0
+            );
+            if let Some(histograms) = &self.histograms {
+                histograms.print();
+            }
+        } else {
+            println!(
+                "Total requests: {}, RPS: {:.2}, Avg latency: {:.2} ms",
+                request_counts,
+                rps,
+                avg_latency / 1000.0
+            );
+        }
+        let size_counts: Vec<usize> = self
+            .size_counts
+            .iter()
+            .map(|c| c.load(Ordering::Relaxed))
+            .collect();
+        // a breakdown with a single bucket says nothing the total does not
+        if size_counts.iter().filter(|c| **c > 0).count() > 1 {
+            for (bucket, count) in size_counts.iter().enumerate().filter(|(_, c)| **c > 0) {
+                let micros = self.size_durations_micros[bucket].load(Ordering::Relaxed);
+                println!(
+                    "  Value size {}: {} requests, Avg latency: {:.2} ms",
+                    size_bucket_label(bucket),
+                    count,
+                    micros as f64 / *count as f64 / 1000.0
+                );
+            }
+        }
+        for (index, count) in self.consistency_counts.iter().enumerate() {
+            let count = count.load(Ordering::Relaxed);
+            if let (Some(consistency), true) = (ConsistencyLevel::from_index(index), count > 0) {
+                let micros = self.consistency_durations_micros[index].load(Ordering::Relaxed);
+                println!(
+                    "  Consistency {}: {} requests, Avg latency: {:.2} ms",
+                    consistency,
+                    count,
+                    micros as f64 / count as f64 / 1000.0
+                );
+            }
+        }
+        let rows = self.row_counts.load(Ordering::Relaxed);
+        if rows > 0 {
+            let micros = self.row_durations_micros.load(Ordering::Relaxed);
+            println!(
+                "  Rows of batches and multi-row reads: {}, Rows per second: {:.2}, Avg latency per row: {:.2} ms",
+                rows,
+                rows as f64 / elapsed,
+                micros as f64 / rows as f64 / 1000.0
+            );
+        }
+        for (bucket, count) in self.tombstone_counts.iter().enumerate() {
+            let count = count.load(Ordering::Relaxed);
+            if count > 0 {
+                let micros = self.tombstone_durations_micros[bucket].load(Ordering::Relaxed);
+                println!(
+                    "  Reads of partitions with {} tombstones: {}, Avg latency: {:.2} ms",
+                    tombstone_bucket_label(bucket),
+                    count,
+                    micros as f64 / count as f64 / 1000.0
+                );
+            }
+        }
+        self.tombstone_alerts.print();
+        self.lwt.print();
+        self.verification.print();
     }
+}
 
-    pub fn report_results(&self, _: QueryType, latency: Duration) -> () {
-        self.request_counts
-            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        self.request_durations_micros.fetch_add(
-            latency.as_micros() as usize,
-            std::sync::atomic::Ordering::Relaxed,
-        );
+impl Reporter for SimpleReporter {
+    fn new(corrected: bool, warmup: Duration) -> Self {
+        SimpleReporter {
+            corrected,
+            clock: Clock::new(warmup),
+            warmup: SimpleStats::new(corrected),
+            measured: SimpleStats::new(corrected),
+        }
+    }
+
+    fn report_results(
+        &self,
+        operation: &Operation,
+        latency: Duration,
+        corrected_latency: Duration,
+    ) {
+        let stats = if self.clock.is_warmup() {
+            &self.warmup
+        } else {
+            &self.measured
+        };
+        stats.record(operation, latency, corrected_latency);
+    }
+
+    fn print_report(&self) {
+        if let Some(elapsed) = self.clock.measured_elapsed() {
+            self.measured.print(self.corrected, elapsed);
+        } else if let Some(elapsed) = self.clock.warmup_elapsed() {
+            print!("Warm-up: ");
+            self.warmup.print(self.corrected, elapsed);
+        }
+    }
+
+    fn stop(&self) {
+        self.clock.stop();
+    }
+
+    fn print_final_report(&self) {
+        if let Some(elapsed) = self.clock.warmup_elapsed().filter(|e| *e > 0.0) {
+            print!("Warm-up, excluded from the results: ");
+            self.warmup.print(self.corrected, elapsed);
+        }
+        if let Some(elapsed) = self.clock.measured_elapsed() {
+            print!("Results: ");
+            self.measured.print(self.corrected, elapsed);
+        }
     }
 }
 
 impl PercentileReporter {
+    /// Latency of all measured operations at the given percentile, corrected for
+    /// coordinated omission if the reporter records corrected latencies
+    pub fn total_latency(&self, percentile: f64) -> Option<Duration> {
+        let state = self.measured.lock().unwrap();
+        let histograms = if self.corrected {
+            &state.corrected_durations
+        } else {
+            &state.request_durations
+        };
+        let bucket = histograms
+            .get(&QueryType::Total)?
+            .percentile(percentile)
+            .ok()??;
+        Some(Duration::from_micros((bucket.start() + bucket.end()) / 2))
+    }
+
+    /// Measured operations per second
+    pub fn throughput(&self) -> Option<f64> {
+        let elapsed = self.clock.measured_elapsed()?;
+        let state = self.measured.lock().unwrap();
+        let count = state.request_counts.get(&QueryType::Total).copied()?;
+        Some(count as f64 / elapsed)
+    }
+
     fn colored_row(row: Vec<String>, color: Color) -> Vec<Cell> {
         row.into_iter().map(|s| Cell::new(s).fg(color)).collect()
     }
 
     fn add_percentile(hist: &Histogram, percentile: f64, row: &mut Vec<String>) {
-        let start = hist.percentile(percentile).unwrap().unwrap().start();
-        let end = hist.percentile(percentile).unwrap().unwrap().end();
-        let between = end + start / 2;
-        row.push(format!("{:.2} ms", between as f64 / 1000.0));
+        match hist.percentile(percentile) {
+            Ok(Some(bucket)) => {
+                let between = (bucket.start() + bucket.end()) / 2;
+                row.push(format!("{:.2} ms", between as f64 / 1000.0));
+            }
+            _ => row.push("-".to_string()),
+        }
+    }
+
+    fn record<K: Ord>(histograms: &mut BTreeMap<K, Histogram>, key: K, latency: Duration) {
+        let hist = histograms
+            .entry(key)
+            .or_insert_with(|| Histogram::new(7, 64).unwrap());
+        if hist.add(latency.as_micros() as u64, 1).is_err() {
+            println!("Failed to add latency to histogram");
+        }
+    }
+
+    /// Table with one row per key, the rows of `highlighted` keys are colored
+    fn latency_table<K: Ord>(
+        first_column: &str,
+        elapsed: f64,
+        counts: &BTreeMap<K, usize>,
+        histograms: &BTreeMap<K, Histogram>,
+        label: impl Fn(&K) -> String,
+        highlighted: impl Fn(&K) -> bool,
+    ) -> Table {
+        let mut table = Table::new();
+        table
+            .load_preset(UTF8_FULL)
+            .set_content_arrangement(ContentArrangement::Dynamic);
+        table.set_header(vec![
+            first_column,
+            "Count",
+            "RPS",
+            "Latency p50",
+            "Latency p75",
+            "Latency p95",
+            "Latency p99",
+        ]);
+        for (key, count) in counts {
+            let Some(hist) = histograms.get(key) else {
+                continue;
+            };
+            let mut row = vec![
+                label(key),
+                Formatter::new().with_decimals(3).format(*count as f64),
+                Formatter::new().format(*count as f64 / elapsed) + " req/s",
+            ];
+            Self::add_percentile(hist, 50.0, &mut row);
+            Self::add_percentile(hist, 75.0, &mut row);
+            Self::add_percentile(hist, 95.0, &mut row);
+            Self::add_percentile(hist, 99.0, &mut row);
+            if highlighted(key) {
+                table.add_row(Self::colored_row(row, Color::Green));
+            } else {
+                table.add_row(row);
+            }
+        }
+        table
+    }
+
+    fn query_type_table(
+        elapsed: f64,
+        counts: &BTreeMap<QueryType, usize>,
+        histograms: &BTreeMap<QueryType, Histogram>,
+    ) -> Table {
+        Self::latency_table(
+            "Query Type",
+            elapsed,
+            counts,
+            histograms,
+            QueryType::to_string,
+            |qt| *qt == QueryType::Total,
+        )
+    }
+
+    /// Applied ratio and timeouts of the query types that ran lightweight transactions
+    fn lwt_table(counts: &BTreeMap<QueryType, LwtCounts>) -> Table {
+        let mut table = Table::new();
+        table
+            .load_preset(UTF8_FULL)
+            .set_content_arrangement(ContentArrangement::Dynamic);
+        table.set_header(vec![
+            "Query Type",
+            "Applied",
+            "Not Applied",
+            "Applied %",
+            "Timeouts",
+        ]);
+        for (qt, counts) in counts {
+            let (applied, not_applied, timeouts) = counts.load();
+            if applied + not_applied + timeouts == 0 {
+                continue;
+            }
+            let row = vec![
+                qt.to_string(),
+                applied.to_string(),
+                not_applied.to_string(),
+                format!(
+                    "{:.1}%",
+                    LwtCounts::applied_percentage(applied, not_applied)
+                ),
+                timeouts.to_string(),
+            ];
+            if *qt == QueryType::Total {
+                table.add_row(Self::colored_row(row, Color::Green));
+            } else {
+                table.add_row(row);
+            }
+        }
+        table
+    }
+
+    fn print_state(&self, state: &PercentileState, elapsed: f64) {
+        let table =
+            Self::query_type_table(elapsed, &state.request_counts, &state.request_durations);
+        if self.corrected {
+            println!("Uncorrected latency (measured from actual send time):");
+            println!("{table}");
+            let corrected =
+                Self::query_type_table(elapsed, &state.request_counts, &state.corrected_durations);
+            println!("Corrected latency (measured from scheduled send time):");
+            println!("{corrected}");
+        } else {
+            println!("{table}");
+        }
+        // a breakdown with a single bucket says nothing the total does not
+        if state.size_counts.len() > 1 {
+            let sizes = Self::latency_table(
+                "Value Size",
+                elapsed,
+                &state.size_counts,
+                &state.size_durations,
+                |bucket| size_bucket_label(*bucket),
+                |_| false,
+            );
+            println!("Latency by value size:");
+            println!("{sizes}");
+        }
+        if !state.consistency_counts.is_empty() {
+            let consistencies = Self::latency_table(
+                "Consistency",
+                elapsed,
+                &state.consistency_counts,
+                &state.consistency_durations,
+                ConsistencyLevel::to_string,
+                |_| false,
+            );
+            println!("Latency by consistency level:");
+            println!("{consistencies}");
+        }
+        if !state.row_counts.is_empty() {
+            let rows = Self::query_type_table(elapsed, &state.row_counts, &state.row_durations);
+            println!("Latency per row of batches and multi-row reads (count and RPS in rows):");
+            println!("{rows}");
+        }
+        if !state.tombstone_counts.is_empty() {
+            let tombstones = Self::latency_table(
+                "Tombstones",
+                elapsed,
+                &state.tombstone_counts,
+                &state.tombstone_durations,
+                |bucket| tombstone_bucket_label(*bucket),
+                |_| false,
+            );
+            println!("Read latency by tombstones written to the partition:");
+            println!("{tombstones}");
+        }
+        state.tombstone_alerts.print();
+        let lwt = Self::lwt_table(&state.lwt);
+        if !lwt.is_empty() {
+            println!("Lightweight transactions:");
+            println!("{lwt}");
+        }
+        state.verification.print();
+        println!();
     }
 }
 
 impl fmt::Display for QueryType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            QueryType::Custom(name) => write!(f, "{}", name),
+            _ => write!(f, "{:?}", self),
+        }
     }
 }
 
-impl PercentileReporter {
-    fn new(period: Duration) -> Self {
+impl Reporter for PercentileReporter {
+    fn new(corrected: bool, warmup: Duration) -> Self {
         PercentileReporter {
-            period,
-            request_counts: BTreeMap::new(),
-            request_durations: BTreeMap::new(),
-            last_reported_at: Instant::now(),
-            first_reported_at: Instant::now(),
-        }
-    }
-
-    fn report_results(&mut self, query_type: QueryType, latency: Duration) -> () {
-        for qt in &vec![QueryType::Total, query_type] {
-            let count = self.request_counts.entry(qt.clone()).or_insert(0);
-            *count += 1;
-            let hist = self
-                .request_durations
-                .entry(qt.clone())
-                .or_insert(Histogram::new(7, 64).unwrap());
-            let latency_as_u64 = (latency.as_secs_f64() * 1000000.0) as u64;
-            let res = hist.add(latency_as_u64, 1);
-            if res.is_err() {
-                println!("Failed to add latency to histogram");
-            }
+            corrected,
+            clock: Clock::new(warmup),
+            warmup: Mutex::new(PercentileState::default()),
+            measured: Mutex::new(PercentileState::default()),
         }
-        if self.last_reported_at.elapsed() > self.period {
-            let mut table = Table::new();
-            table
-                .load_preset(UTF8_FULL)
-                .set_content_arrangement(ContentArrangement::Dynamic);
-            let mut header = Vec::new();
-            header.push("Query Type");
-            header.push("Count");
-            header.push("RPS");
-            header.push("Latency p50");
-            header.push("Latency p75");
-            header.push("Latency p95");
-            header.push("Latency p99");
-            table.set_header(header);
-            for (query_type, count) in &self.request_counts {
-                let hist = self.request_durations.get(query_type).unwrap();
-                let mut row = Vec::new();
-                row.push(format!("{:?}", query_type));
-                row.push(Formatter::new().with_decimals(3).format(*count as f64));
-                let rps = *count as f64 / self.first_reported_at.elapsed().as_secs_f64();
-                row.push(Formatter::new().format(rps) + " req/s");
-                Self::add_percentile(hist, 50.0, &mut row);
-                Self::add_percentile(hist, 75.0, &mut row);
-                Self::add_percentile(hist, 95.0, &mut row);
-                Self::add_percentile(hist, 99.0, &mut row);
-                if *query_type == QueryType::Total {
-                    table.add_row(Self::colored_row(row, Color::Green));
-                } else {
-                    table.add_row(row);
-                }
+    }
+
+    fn report_results(
+        &self,
+        operation: &Operation,
+        latency: Duration,
+        corrected_latency: Duration,
+    ) {
+        let mut state = if self.clock.is_warmup() {
+            self.warmup.lock().unwrap()
+        } else {
+            self.measured.lock().unwrap()
+        };
+        for qt in [QueryType::Total, operation.query_type] {
+            state.lwt.entry(qt).or_default().record(operation);
+        }
+        state.tombstone_alerts.record(operation.tombstone_alert);
+        if !operation.has_latency() {
+            return;
+        }
+        for qt in [QueryType::Total, operation.query_type] {
+            *state.request_counts.entry(qt).or_insert(0) += 1;
+            Self::record(&mut state.request_durations, qt, latency);
+            if self.corrected {
+                Self::record(&mut state.corrected_durations, qt, corrected_latency);
             }
-            println!("{table}\n");
-            self.last_reported_at = Instant::now();
+        }
+        if let Some(size) = operation.value_size {
+            let bucket = size_bucket(size);
+            *state.size_counts.entry(bucket).or_insert(0) += 1;
+            Self::record(&mut state.size_durations, bucket, latency);
+        }
+        if let Some(consistency) = operation.consistency {
+            *state.consistency_counts.entry(consistency).or_insert(0) += 1;
+            Self::record(&mut state.consistency_durations, consistency, latency);
+        }
+        if let Some(rows) = operation.rows.filter(|rows| *rows > 0) {
+            let qt = operation.query_type;
+            *state.row_counts.entry(qt).or_insert(0) += rows;
+            Self::record(&mut state.row_durations, qt, latency / rows as u32);
+        }
+        if let Some(tombstones) = operation.tombstones {
+            let bucket = tombstone_bucket(tombstones);
+            *state.tombstone_counts.entry(bucket).or_insert(0) += 1;
+            Self::record(&mut state.tombstone_durations, bucket, latency);
+        }
+        state.verification.record(operation.verification);
+    }
+
+    fn print_report(&self) {
+        if let Some(elapsed) = self.clock.measured_elapsed() {
+            self.print_state(&self.measured.lock().unwrap(), elapsed);
+        } else if let Some(elapsed) = self.clock.warmup_elapsed() {
+            println!("Warm-up:");
+            self.print_state(&self.warmup.lock().unwrap(), elapsed);
+        }
+    }
+
+    fn stop(&self) {
+        self.clock.stop();
+    }
+
+    fn print_final_report(&self) {
+        if let Some(elapsed) = self.clock.warmup_elapsed().filter(|e| *e > 0.0) {
+            println!("Warm-up, excluded from the results:");
+            self.print_state(&self.warmup.lock().unwrap(), elapsed);
+        }
+        if let Some(elapsed) = self.clock.measured_elapsed() {
+            println!("Results:");
+            self.print_state(&self.measured.lock().unwrap(), elapsed);
         }
     }
 }
@@ -0,0 +1,321 @@
+use crate::consistency::StatementConsistency;
+use anyhow::{bail, Context, Result};
+use scylla::prepared_statement::PreparedStatement;
+use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
+use std::fmt;
+use std::str::FromStr;
+
+/// Keyspace, table names and table options of the built-in workloads
+#[derive(Clone, Debug)]
+pub struct SchemaConfig {
+    pub keyspace: String,
+    /// Name of the workload table, each workload has its own default
+    pub table: Option<String>,
+    pub replication: Replication,
+    /// Whether the keyspace uses tablets, the cluster default if not set
+    pub tablets: Option<bool>,
+    pub compaction: Option<TableOption>,
+    pub compression: Option<TableOption>,
+    pub caching: Option<TableOption>,
+}
+
+/// Replication of the keyspace, parsed from a replication factor for `SimpleStrategy`, e.g.
+/// `3`, or from per-datacenter factors for `NetworkTopologyStrategy`, e.g. `dc1:3,dc2:2`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Replication {
+    Simple(u32),
+    Network(Vec<(String, u32)>),
+}
+
+/// Table option map, parsed from `[<class>][,<key>=<value>...]`, e.g.
+/// `LeveledCompactionStrategy,sstable_size_in_mb=160` or `enabled=false`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableOption {
+    pub class: Option<String>,
+    pub params: Vec<(String, String)>,
+}
+
+impl Default for SchemaConfig {
+    fn default() -> Self {
+        SchemaConfig {
+            keyspace: "test".to_string(),
+            table: None,
+            replication: Replication::Simple(1),
+            tablets: None,
+            compaction: None,
+            compression: None,
+            caching: None,
+        }
+    }
+}
+
+impl SchemaConfig {
+    /// Name of the workload table, `default` unless the name is configured
+    pub fn table_name<'a>(&'a self, default: &'a str) -> &'a str {
+        self.table.as_deref().unwrap_or(default)
+    }
+
+    /// Qualified name of the workload table
+    pub fn table(&self, default: &str) -> String {
+        self.qualified(self.table_name(default))
+    }
+
+    /// Qualified name of an object of the keyspace
+    pub fn qualified(&self, name: &str) -> String {
+        format!("{}.{}", self.keyspace, name)
+    }
+
+    pub fn create_keyspace(&self) -> String {
+        let mut cql = format!(
+            "CREATE KEYSPACE IF NOT EXISTS {} WITH REPLICATION = {}",
+            self.keyspace, self.replication
+        );
+        if let Some(tablets) = self.tablets {
+            cql += &format!(" AND TABLETS = {{ 'enabled' : {} }}", tablets);
+        }
+        cql
+    }
+
+    /// `CREATE TABLE` statement of the table with the given columns and primary key, with the
+    /// options of the workload, e.g. a clustering order, followed by the configured ones
+    pub fn create_table(&self, table: &str, definition: &str, options: &[String]) -> String {
+        let mut options = options.to_vec();
+        if let Some(compaction) = &self.compaction {
+            options.push(format!("compaction = {}", compaction.to_cql("class")));
+        }
+        if let Some(compression) = &self.compression {
+            options.push(format!(
+                "compression = {}",
+                compression.to_cql("sstable_compression")
+            ));
+        }
+        if let Some(caching) = &self.caching {
+            options.push(format!("caching = {}", caching.to_cql("enabled")));
+        }
+        let mut cql = format!("CREATE TABLE IF NOT EXISTS {} ({})", table, definition);
+        if !options.is_empty() {
+            cql += &format!(" WITH {}", options.join(" AND "));
+        }
+        cql
+    }
+
+    /// Statement that drops what a workload created: only its table if the table name is
+    /// configured, as the keyspace may then be shared with other tables, the whole keyspace
+    /// otherwise
+    pub fn drop_schema(&self, default_table: &str) -> String {
+        match self.table {
+            Some(_) => format!("DROP TABLE IF EXISTS {}", self.table(default_table)),
+            None => format!("DROP KEYSPACE IF EXISTS {}", self.keyspace),
+        }
+    }
+
+    /// Create the keyspace, then run the statements creating the objects of the workload in
+    /// order, e.g. its types and its table
+    pub async fn create(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        statements: &[String],
+    ) -> Result<()> {
+        session.query_unpaged(self.create_keyspace(), &[]).await?;
+        for statement in statements {
+            session.query_unpaged(statement.as_str(), &[]).await?;
+        }
+        Ok(())
+    }
+
+    /// Prepare a statement of the workload with the given consistency settings
+    pub async fn prepare(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        cql: String,
+        consistency: StatementConsistency,
+    ) -> Result<PreparedStatement> {
+        let mut statement = session.prepare(cql).await?;
+        consistency.apply(&mut statement);
+        Ok(statement)
+    }
+}
+
+impl TableOption {
+    /// CQL map of the option, with the class under `class_key`. A class of `none` is
+    /// rendered empty, which is how compression is turned off
+    fn to_cql(&self, class_key: &str) -> String {
+        let class = self.class.as_ref().map(|class| {
+            let class = if class.eq_ignore_ascii_case("none") {
+                ""
+            } else {
+                class
+            };
+            (class_key, class)
+        });
+        let entries: Vec<String> = class
+            .into_iter()
+            .chain(self.params.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+            .map(|(key, value)| format!("'{}' : '{}'", key, value))
+            .collect();
+        format!("{{ {} }}", entries.join(", "))
+    }
+}
+
+impl FromStr for Replication {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Ok(factor) = s.trim().parse() {
+            return Ok(Replication::Simple(factor));
+        }
+        let factors = s
+            .split(',')
+            .map(|dc| {
+                let (name, factor) = dc.split_once(':')?;
+                Some((name.trim().to_string(), factor.trim().parse().ok()?))
+            })
+            .collect::<Option<Vec<_>>>()
+            .with_context(|| {
+                format!(
+                    "Invalid replication '{}', expected <replication factor> or <datacenter>:<replication factor>[,...]",
+                    s
+                )
+            })?;
+        Ok(Replication::Network(factors))
+    }
+}
+
+impl fmt::Display for Replication {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Replication::Simple(factor) => write!(
+                f,
+                "{{ 'class' : 'SimpleStrategy', 'replication_factor' : {} }}",
+                factor
+            ),
+            Replication::Network(factors) => {
+                write!(f, "{{ 'class' : 'NetworkTopologyStrategy'")?;
+                for (dc, factor) in factors {
+                    write!(f, ", '{}' : {}", dc, factor)?;
+                }
+                write!(f, " }}")
+            }
+        }
+    }
+}
+
+impl FromStr for TableOption {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut option = TableOption {
+            class: None,
+            params: Vec::new(),
+        };
+        for (i, item) in s.split(',').map(str::trim).enumerate() {
+            match item.split_once('=') {
+                Some((key, value)) => option
+                    .params
+                    .push((key.trim().to_string(), value.trim().to_string())),
+                None if i == 0 && !item.is_empty() => option.class = Some(item.to_string()),
+                None => bail!(
+                    "Invalid table option '{}', expected [<class>][,<key>=<value>...]",
+                    s
+                ),
+            }
+        }
+        Ok(option)
+    }
+}
+
+impl fmt::Display for TableOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let items: Vec<String> = self
+            .class
+            .iter()
+            .cloned()
+            .chain(self.params.iter().map(|(k, v)| format!("{}={}", k, v)))
+            .collect();
+        write!(f, "{}", items.join(","))
+    }
+}
+
+impl fmt::Display for SchemaConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "keyspace: {}, replication: ", self.keyspace)?;
+        match &self.replication {
+            Replication::Simple(factor) => write!(f, "{}", factor)?,
+            Replication::Network(factors) => {
+                let factors: Vec<String> = factors
+                    .iter()
+                    .map(|(dc, rf)| format!("{}:{}", dc, rf))
+                    .collect();
+                write!(f, "{}", factors.join(","))?
+            }
+        }
+        let option =
+            |o: &Option<TableOption>| o.as_ref().map_or("default".to_string(), |o| o.to_string());
+        write!(
+            f,
+            ", tablets: {}, table: {}, compaction: {}, compression: {}, caching: {}",
+            self.tablets
+                .map_or("default".to_string(), |t| t.to_string()),
+            self.table.as_deref().unwrap_or("default"),
+            option(&self.compaction),
+            option(&self.compression),
+            option(&self.caching)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_replication() {
+        assert_eq!("3".parse::<Replication>().unwrap(), Replication::Simple(3));
+        assert_eq!(
+            "dc1:3, dc2 : 2".parse::<Replication>().unwrap(),
+            Replication::Network(vec![("dc1".to_string(), 3), ("dc2".to_string(), 2)])
+        );
+        assert_eq!(
+            Replication::Network(vec![("dc1".to_string(), 3)]).to_string(),
+            "{ 'class' : 'NetworkTopologyStrategy', 'dc1' : 3 }"
+        );
+        for s in ["", "-1", "dc1", "dc1:three", "dc1:3,", "dc1:3,dc2"] {
+            assert!(s.parse::<Replication>().is_err(), "{} was accepted", s);
+        }
+    }
+
+    #[test]
+    fn parses_table_options() {
+        let option: TableOption = "LeveledCompactionStrategy, sstable_size_in_mb=160"
+            .parse()
+            .unwrap();
+        assert_eq!(option.class.as_deref(), Some("LeveledCompactionStrategy"));
+        assert_eq!(
+            option.params,
+            [("sstable_size_in_mb".to_string(), "160".to_string())]
+        );
+        assert_eq!(
+            option.to_cql("class"),
+            "{ 'class' : 'LeveledCompactionStrategy', 'sstable_size_in_mb' : '160' }"
+        );
+        let option: TableOption = "enabled=false".parse().unwrap();
+        assert_eq!(option.class, None);
+        assert_eq!(option.to_string(), "enabled=false");
+        let none: TableOption = "none".parse().unwrap();
+        assert_eq!(
+            none.to_cql("sstable_compression"),
+            "{ 'sstable_compression' : '' }"
+        );
+        for s in ["", "a=1,LZ4Compressor", "LZ4Compressor,,a=1"] {
+            assert!(s.parse::<TableOption>().is_err(), "{} was accepted", s);
+        }
+    }
+
+    #[test]
+    fn drops_only_the_configured_table() {
+        let mut schema = SchemaConfig::default();
+        assert_eq!(schema.drop_schema("kv"), "DROP KEYSPACE IF EXISTS test");
+        schema.table = Some("mine".to_string());
+        assert_eq!(schema.drop_schema("kv"), "DROP TABLE IF EXISTS test.mine");
+    }
+}
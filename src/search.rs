@@ -0,0 +1,183 @@
+use crate::args::SearchArgs;
+use crate::distribution::KeySlice;
+use crate::executor::{Executor, ExecutorConfig};
+use crate::lifecycle::SchemaLifecycle;
+use crate::profile::Profile;
+use crate::reporter::{PercentileReporter, Reporter};
+use crate::signals::Signals;
+use anyhow::{bail, Result};
+use comfy_table::presets::UTF8_FULL;
+use comfy_table::{Cell, Color, ContentArrangement, Table};
+use human_format::Formatter;
+use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A step only passes if the cluster kept up with at least this share of the target rate
+const MIN_ACHIEVED_RATE: f64 = 0.95;
+
+struct Step {
+    rate: f64,
+    throughput: f64,
+    p50: Option<Duration>,
+    slo_latency: Option<Duration>,
+    passed: bool,
+}
+
+/// Find the highest rate that meets the latency SLO. The rate is doubled until a step
+/// fails, then the range between the last passed and the first failed rate is bisected.
+/// A stop signal ends the search with the steps finished so far
+pub async fn search(
+    args: SearchArgs,
+    session: Arc<GenericSession<CurrentDeserializationApi>>,
+    signals: Signals,
+) -> Result<()> {
+    args.validate()?;
+    if args.workload.replay.is_some() {
+        bail!("The search sets the rate of every step, a trace is replayed at its recorded timing");
+    }
+    let profile = args.workload.load_profile()?;
+    // the steps run against the existing dataset, the schema is kept once the search is over
+    SchemaLifecycle::setup(args.workload.build(profile.clone())?, &session, false).await?;
+    let mut steps = Vec::new();
+    let mut passed: Option<f64> = None;
+    let mut failed: Option<f64> = None;
+    let mut rate = args.min_rate;
+    loop {
+        let step = run_step(&args, profile.clone(), session.clone(), rate, &signals).await?;
+        if signals.stopping() {
+            println!(
+                "Search interrupted, leaving out the unfinished step at {:.0} ops/s",
+                rate
+            );
+            break;
+        }
+        println!(
+            "Step {}: target {:.0} ops/s, achieved {:.0} ops/s, p{} {}: {}",
+            steps.len() + 1,
+            step.rate,
+            step.throughput,
+            args.slo_percentile,
+            format_latency(step.slo_latency),
+            if step.passed { "passed" } else { "failed" }
+        );
+        if step.passed {
+            passed = Some(rate);
+        } else {
+            failed = Some(rate);
+        }
+        steps.push(step);
+        rate = match (passed, failed) {
+            (Some(low), None) if low >= args.max_rate => break,
+            (Some(low), None) => (low * 2.0).min(args.max_rate),
+            (None, _) => break,
+            (Some(low), Some(high)) if high / low <= 1.0 + args.precision => break,
+            (Some(low), Some(high)) => (low + high) / 2.0,
+        };
+    }
+    print_steps(&args, &mut steps);
+    match passed {
+        Some(rate) => println!(
+            "Max sustainable rate with p{} <= {:?}: {:.0} ops/s",
+            args.slo_percentile, args.slo_latency, rate
+        ),
+        None => println!(
+            "No rate meets p{} <= {:?}, even the minimum rate of {:.0} ops/s fails",
+            args.slo_percentile, args.slo_latency, args.min_rate
+        ),
+    }
+    Ok(())
+}
+
+async fn run_step(
+    args: &SearchArgs,
+    profile: Option<Profile>,
+    session: Arc<GenericSession<CurrentDeserializationApi>>,
+    rate: f64,
+    signals: &Signals,
+) -> Result<Step> {
+    println!("Running {:.0} ops/s for {:?}...", rate, args.step_duration);
+    let reporter = Arc::new(PercentileReporter::new(true, args.step_warmup));
+    let executor = Executor::new(
+        ExecutorConfig {
+            concurrency: args.concurrency,
+            rate: Some(rate),
+            slice: KeySlice::default(),
+            drain_timeout: None,
+            stop_on_corruption: false,
+            verify_after_run: false,
+            report_period: args.step_duration,
+        },
+        args.workload.build(profile)?,
+        reporter.clone(),
+    );
+    let (stop_sender, executor_thread) = executor.start(session).await?;
+    tokio::select! {
+        _ = tokio::time::sleep(args.step_warmup + args.step_duration) => {}
+        _ = signals.stopped() => {}
+    }
+    if let Err(e) = stop_sender.send(()) {
+        println!("Error sending stop signal: {:?}", e);
+    }
+    executor_thread.await??;
+    let throughput = reporter.throughput().unwrap_or(0.0);
+    let slo_latency = reporter.total_latency(args.slo_percentile);
+    let passed = throughput >= rate * MIN_ACHIEVED_RATE
+        && slo_latency.is_some_and(|latency| latency <= args.slo_latency);
+    Ok(Step {
+        rate,
+        throughput,
+        p50: reporter.total_latency(50.0),
+        slo_latency,
+        passed,
+    })
+}
+
+fn format_latency(latency: Option<Duration>) -> String {
+    match latency {
+        Some(latency) => format!("{:.2} ms", latency.as_secs_f64() * 1000.0),
+        None => "-".to_string(),
+    }
+}
+
+/// Throughput versus latency table ordered by rate, the max sustainable step is highlighted
+fn print_steps(args: &SearchArgs, steps: &mut [Step]) {
+    steps.sort_by(|a, b| a.rate.total_cmp(&b.rate));
+    let best = steps
+        .iter()
+        .filter(|step| step.passed)
+        .map(|step| step.rate)
+        .reduce(f64::max);
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(ContentArrangement::Dynamic);
+    table.set_header(vec![
+        "Target Rate".to_string(),
+        "Throughput".to_string(),
+        "Latency p50".to_string(),
+        format!("Latency p{}", args.slo_percentile),
+        "SLO".to_string(),
+    ]);
+    for step in steps.iter() {
+        let row = vec![
+            Formatter::new().format(step.rate) + " req/s",
+            Formatter::new().format(step.throughput) + " req/s",
+            format_latency(step.p50),
+            format_latency(step.slo_latency),
+            if Some(step.rate) == best {
+                "max sustainable".to_string()
+            } else if step.passed {
+                "passed".to_string()
+            } else {
+                "failed".to_string()
+            },
+        ];
+        if Some(step.rate) == best {
+            table.add_row(row.into_iter().map(|s| Cell::new(s).fg(Color::Green)));
+        } else {
+            table.add_row(row);
+        }
+    }
+    println!("{table}");
+}
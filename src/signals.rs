@@ -0,0 +1,67 @@
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{watch, Notify};
+
+/// Signals of the process, handled from the start of a command until the process exits:
+/// - the first SIGINT or SIGTERM requests a graceful stop: the population stops taking new
+///   keys and writes its checkpoint, the benchmark stops as if its duration had passed, and
+///   the final report and cleanup run as usual
+/// - the second one aborts the process immediately, without the final report and cleanup
+/// - SIGUSR1 requests an interim report, the command goes on
+#[derive(Clone)]
+pub struct Signals {
+    stop: watch::Receiver<bool>,
+    report: Arc<Notify>,
+}
+
+impl Signals {
+    pub fn install() -> Result<Signals> {
+        let mut interrupt = signal(SignalKind::interrupt()).context("Failed to handle SIGINT")?;
+        let mut terminate = signal(SignalKind::terminate()).context("Failed to handle SIGTERM")?;
+        let mut user1 = signal(SignalKind::user_defined1()).context("Failed to handle SIGUSR1")?;
+        let (stop_sender, stop) = watch::channel(false);
+        let report = Arc::new(Notify::new());
+        let report_sender = report.clone();
+        tokio::spawn(async move {
+            loop {
+                // exit codes of an abort follow the shell convention, 128 + the signal number
+                let (name, exit_code) = tokio::select! {
+                    _ = interrupt.recv() => ("SIGINT", 130),
+                    _ = terminate.recv() => ("SIGTERM", 143),
+                    _ = user1.recv() => {
+                        report_sender.notify_waiters();
+                        continue;
+                    }
+                };
+                if *stop_sender.borrow() {
+                    println!("Received {} again, aborting", name);
+                    std::process::exit(exit_code);
+                }
+                println!(
+                    "Received {}, stopping, send it again to abort immediately",
+                    name
+                );
+                stop_sender.send_replace(true);
+            }
+        });
+        Ok(Signals { stop, report })
+    }
+
+    /// Whether a graceful stop was requested
+    pub fn stopping(&self) -> bool {
+        *self.stop.borrow()
+    }
+
+    /// Wait until a graceful stop is requested
+    pub async fn stopped(&self) {
+        let mut stop = self.stop.clone();
+        // the sender is owned by the signal task, which runs until the process exits
+        let _ = stop.wait_for(|stopping| *stopping).await;
+    }
+
+    /// Wait for the next request of an interim report
+    pub async fn report_requested(&self) {
+        self.report.notified().await
+    }
+}
@@ -0,0 +1,178 @@
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Operations recorded in a trace file, replayed with `--replay`.
+///
+/// Every line holds the start offset of the operation in seconds since the start of the
+/// recording, the operation (`read`, `write` or `delete`), the key and the value size in
+/// bytes. Empty lines and lines starting with `#` are skipped:
+/// ```text
+/// # offset,operation,key,value_size
+/// 0.000000,write,user:1042,512
+/// 0.000350,read,user:1042,512
+/// 0.001200,delete,user:7,0
+/// ```
+pub struct Trace {
+    entries: Vec<TraceEntry>,
+}
+
+pub struct TraceEntry {
+    /// Start of the operation since the start of the replay, already scaled by the speed
+    pub offset: Duration,
+    pub operation: TraceOperation,
+    pub key: String,
+    pub value_size: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceOperation {
+    Read,
+    Write,
+    Delete,
+}
+
+impl Trace {
+    /// Load a trace, dividing the offsets by `speed`: 2.0 replays twice as fast as recorded.
+    /// Entries are ordered by offset, the first one starts the replay
+    pub fn load(path: &Path, speed: f64) -> Result<Trace> {
+        if !(speed > 0.0 && speed.is_finite()) {
+            bail!("Replay speed must be greater than 0, got {}", speed);
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read trace {}", path.display()))?;
+        let mut entries = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = parse_entry(line, speed)
+                .with_context(|| format!("{}:{}: '{}'", path.display(), number + 1, line))?;
+            entries.push(entry);
+        }
+        if entries.is_empty() {
+            bail!("Trace {} has no operations", path.display());
+        }
+        entries.sort_by_key(|e| e.offset);
+        let start = entries[0].offset;
+        for entry in &mut entries {
+            entry.offset -= start;
+        }
+        Ok(Trace { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn entry(&self, n: usize) -> &TraceEntry {
+        &self.entries[n]
+    }
+
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+
+    /// Time from the first to the last operation
+    pub fn duration(&self) -> Duration {
+        self.entries.last().map_or(Duration::ZERO, |e| e.offset)
+    }
+}
+
+fn parse_entry(line: &str, speed: f64) -> Result<TraceEntry> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    let [offset, operation, key, value_size] = fields[..] else {
+        bail!("Expected 4 fields: <offset>,<operation>,<key>,<value size>");
+    };
+    let offset: f64 = offset
+        .parse()
+        .with_context(|| format!("Invalid offset '{}'", offset))?;
+    let offset = Duration::try_from_secs_f64(offset / speed)
+        .with_context(|| format!("Invalid offset '{}'", offset))?;
+    if key.is_empty() {
+        bail!("Key is empty");
+    }
+    Ok(TraceEntry {
+        offset,
+        operation: operation.parse()?,
+        key: key.to_string(),
+        value_size: value_size
+            .parse()
+            .with_context(|| format!("Invalid value size '{}'", value_size))?,
+    })
+}
+
+impl FromStr for TraceOperation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "read" => Ok(TraceOperation::Read),
+            "write" => Ok(TraceOperation::Write),
+            "delete" => Ok(TraceOperation::Delete),
+            _ => bail!("Unknown operation '{}', available: read, write, delete", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_entries() {
+        let entry = parse_entry(" 1.5, WRITE ,user:1042, 512", 1.0).unwrap();
+        assert_eq!(entry.offset, Duration::from_millis(1500));
+        assert_eq!(entry.operation, TraceOperation::Write);
+        assert_eq!(entry.key, "user:1042");
+        assert_eq!(entry.value_size, 512);
+        let entry = parse_entry("1.5,delete,user:7,0", 3.0).unwrap();
+        assert_eq!(entry.offset, Duration::from_millis(500));
+        assert_eq!(entry.operation, TraceOperation::Delete);
+    }
+
+    #[test]
+    fn rejects_invalid_entries() {
+        for line in [
+            "0.1,read,user:1",
+            "0.1,read,user:1,10,extra",
+            "soon,read,user:1,10",
+            "-1,read,user:1,10",
+            "0.1,update,user:1,10",
+            "0.1,read,,10",
+            "0.1,read,user:1,-10",
+        ] {
+            assert!(parse_entry(line, 1.0).is_err(), "{} was accepted", line);
+        }
+    }
+
+    #[test]
+    fn loads_sorted_entries_starting_at_zero() {
+        let path =
+            std::env::temp_dir().join(format!("scylla-perf-test-{}.trace", std::process::id()));
+        std::fs::write(
+            &path,
+            "# offset,operation,key,value_size\n\
+             \n\
+             2.0,read,b,10\n\
+             \x20\x20# indented comment\n\
+             1.0,write,a,10\n\
+             \n\
+             3.0,delete,c,0\n",
+        )
+        .unwrap();
+        let trace = Trace::load(&path, 2.0).unwrap();
+        let keys: Vec<&str> = trace.entries().iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, ["a", "b", "c"]);
+        assert_eq!(trace.entry(0).offset, Duration::ZERO);
+        assert_eq!(trace.duration(), Duration::from_secs(1));
+        std::fs::write(&path, "# only a comment\n\n").unwrap();
+        assert!(Trace::load(&path, 1.0).is_err());
+        std::fs::write(&path, "1.0,write,a,10\n1.0,write,a\n").unwrap();
+        assert!(Trace::load(&path, 1.0).is_err());
+        assert!(Trace::load(&path, 0.0).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
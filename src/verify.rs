@@ -0,0 +1,132 @@
+use crate::workload::{Verification, Workload};
+use anyhow::{Context, Result};
+use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+#[derive(Default)]
+pub struct VerifyStats {
+    pub checked: AtomicUsize,
+    pub missing: AtomicUsize,
+    pub mismatched: AtomicUsize,
+    pub errors: AtomicUsize,
+}
+
+impl VerifyStats {
+    pub fn is_ok(&self) -> bool {
+        self.missing.load(Ordering::Relaxed) == 0
+            && self.mismatched.load(Ordering::Relaxed) == 0
+            && self.errors.load(Ordering::Relaxed) == 0
+    }
+
+    fn print(&self, total: usize, started_at: Instant) {
+        let checked = self.checked.load(Ordering::Relaxed);
+        println!(
+            "Verified {}/{} keys, {:.2} keys/s, missing: {}, mismatched: {}, errors: {}",
+            checked,
+            total,
+            checked as f64 / started_at.elapsed().as_secs_f64(),
+            self.missing.load(Ordering::Relaxed),
+            self.mismatched.load(Ordering::Relaxed),
+            self.errors.load(Ordering::Relaxed)
+        );
+    }
+}
+
+/// Read the rows with the given indexes back and compare them with the values the workload
+/// expects, `0..workload.population_size()` for the whole dataset
+pub async fn verify(
+    workload: Arc<dyn Workload>,
+    session: Arc<GenericSession<CurrentDeserializationApi>>,
+    indexes: Range<usize>,
+    concurrency: usize,
+    report_period: Duration,
+) -> Result<Arc<VerifyStats>> {
+    let total = indexes.len();
+    let end = indexes.end;
+    let stats = Arc::new(VerifyStats::default());
+    let next_index = Arc::new(AtomicUsize::new(indexes.start));
+    let started_at = Instant::now();
+    let mut workers = Vec::new();
+    for _ in 0..concurrency.max(1) {
+        let workload = workload.clone();
+        let session = session.clone();
+        let stats = stats.clone();
+        let next_index = next_index.clone();
+        workers.push(tokio::spawn(async move {
+            loop {
+                let index = next_index.fetch_add(1, Ordering::Relaxed);
+                if index >= end {
+                    return;
+                }
+                match workload.verify(&session, index).await {
+                    Ok(Verification::Ok) => {}
+                    Ok(Verification::Missing) => {
+                        println!("Key {} is missing", index);
+                        stats.missing.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Ok(Verification::Mismatch) => {
+                        println!("Key {} has an unexpected value", index);
+                        stats.mismatched.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(e) => {
+                        println!("Error verifying key {}: {:?}", index, e);
+                        stats.errors.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                stats.checked.fetch_add(1, Ordering::Relaxed);
+            }
+        }));
+    }
+    let reporter_stats = stats.clone();
+    let reporter = tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(report_period).await;
+            reporter_stats.print(total, started_at);
+        }
+    });
+    for worker in workers {
+        worker.await?;
+    }
+    reporter.abort();
+    stats.print(total, started_at);
+    Ok(stats)
+}
+
+/// Read the initial values of the rows with the given indexes before the benchmark, see
+/// `Workload::read_initial`. Fails on the first row that cannot be read, the run could not
+/// be verified without it
+pub async fn read_initial_values(
+    workload: Arc<dyn Workload>,
+    session: Arc<GenericSession<CurrentDeserializationApi>>,
+    indexes: Range<usize>,
+    concurrency: usize,
+) -> Result<()> {
+    let end = indexes.end;
+    let next_index = Arc::new(AtomicUsize::new(indexes.start));
+    let mut workers = Vec::new();
+    for _ in 0..concurrency.max(1) {
+        let workload = workload.clone();
+        let session = session.clone();
+        let next_index = next_index.clone();
+        workers.push(tokio::spawn(async move {
+            loop {
+                let index = next_index.fetch_add(1, Ordering::Relaxed);
+                if index >= end {
+                    return Ok::<_, anyhow::Error>(());
+                }
+                workload
+                    .read_initial(&session, index)
+                    .await
+                    .with_context(|| format!("Error reading the initial value of key {}", index))?;
+            }
+        }));
+    }
+    for worker in workers {
+        worker.await??;
+    }
+    Ok(())
+}
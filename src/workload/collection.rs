@@ -0,0 +1,488 @@
+use crate::consistency::StatementConsistency;
+use crate::distribution::{KeyDistribution, KeySampler, KeySlice, ValueSizeDistribution};
+use crate::generator::index_rng;
+use crate::reporter::{Operation, QueryType};
+use crate::schema::SchemaConfig;
+use crate::workload::{parse_weights, Workload};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use rand::distributions::{Alphanumeric, DistString, Distribution, WeightedIndex};
+use rand::Rng;
+use scylla::frame::response::result::CqlValue;
+use scylla::prepared_statement::PreparedStatement;
+use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
+use std::fmt;
+use std::str::FromStr;
+
+/// Type of the collection column
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollectionType {
+    /// `list<text>`
+    List,
+    /// `set<text>`
+    Set,
+    /// `map<int, text>`
+    Map,
+    /// `map<int, frozen<item>>` of the user-defined type `item`
+    Udt,
+    /// Non-frozen `item` column, a single value whose fields are updated separately
+    UdtColumn,
+}
+
+/// Shape of the collections and the operations on them
+#[derive(Clone, Debug)]
+pub struct CollectionConfig {
+    pub collection_type: CollectionType,
+    /// Store the whole collection as a single frozen value, it can only be overwritten
+    pub frozen: bool,
+    /// Number of elements a collection is overwritten with. A UDT column holds one value,
+    /// chosen out of twice this many
+    pub size: usize,
+    pub mix: CollectionMix,
+}
+
+/// Relative weights of the operations, parsed from `<append>,<remove>,<overwrite>,<read>`,
+/// e.g. `4,1,1,4`
+#[derive(Clone, Debug, PartialEq)]
+pub struct CollectionMix {
+    pub append: u32,
+    pub remove: u32,
+    pub overwrite: u32,
+    pub read: u32,
+}
+
+/// Single-row partitions with a text key and a collection column. Appends add an element,
+/// removes take one out, overwrites replace the whole collection with `size` elements and
+/// reads fetch it. Elements are regenerated from the seed, element `j` of a key always has
+/// the same value, and appends and removes pick `j` out of twice the collection size, so
+/// they hit elements that are there as well as ones that are not. A UDT column is not a
+/// collection: overwrites replace it with element `j`, appends set its `payload` field to
+/// the one of element `j` and removes clear the field.
+pub struct CollectionWorkload {
+    total_keys: usize,
+    key_string_length: usize,
+    element_sizes: ValueSizeDistribution,
+    seed: u64,
+    config: CollectionConfig,
+    operations: WeightedIndex<u32>,
+    read_consistency: StatementConsistency,
+    write_consistency: StatementConsistency,
+    read_keys: KeySampler,
+    write_keys: KeySampler,
+    statements: Option<Statements>,
+    schema: SchemaConfig,
+}
+
+struct Statements {
+    append: PreparedStatement,
+    remove: PreparedStatement,
+    overwrite: PreparedStatement,
+    read: PreparedStatement,
+}
+
+/// Query types of the operations, in the order of their weights
+const OPERATIONS: [QueryType; 4] = [
+    QueryType::Append,
+    QueryType::Remove,
+    QueryType::Overwrite,
+    QueryType::Read,
+];
+
+impl CollectionWorkload {
+    pub fn new(
+        total_keys: usize,
+        key_string_length: usize,
+        element_sizes: &ValueSizeDistribution,
+        read_distribution: &KeyDistribution,
+        write_distribution: &KeyDistribution,
+        seed: u64,
+        config: CollectionConfig,
+    ) -> Result<CollectionWorkload> {
+        let mix = &config.mix;
+        let mut weights = [mix.append, mix.remove, mix.overwrite, mix.read];
+        if config.frozen {
+            println!("Frozen collections can only be overwritten, ignoring the append and remove weights");
+            weights[0] = 0;
+            weights[1] = 0;
+        }
+        let operations = WeightedIndex::new(weights)?;
+        println!(
+            "Using {total_keys} {} of {} elements, key length: {key_string_length}, element size: {element_sizes}, seed: {seed}",
+            config.column_type(),
+            config.size
+        );
+        Ok(CollectionWorkload {
+            total_keys,
+            key_string_length,
+            element_sizes: element_sizes.clone(),
+            seed,
+            config,
+            operations,
+            read_consistency: StatementConsistency::default(),
+            write_consistency: StatementConsistency::default(),
+            read_keys: read_distribution.sampler(total_keys),
+            write_keys: write_distribution.sampler(total_keys),
+            statements: None,
+            schema: SchemaConfig::default(),
+        })
+    }
+
+    /// Consistency settings of the read and the write statements
+    pub fn with_consistency(
+        mut self,
+        read: StatementConsistency,
+        write: StatementConsistency,
+    ) -> CollectionWorkload {
+        self.read_consistency = read;
+        self.write_consistency = write;
+        self
+    }
+
+    /// Keyspace, table name and table options
+    pub fn with_schema(mut self, schema: SchemaConfig) -> CollectionWorkload {
+        self.schema = schema;
+        self
+    }
+
+    /// Key with the given index
+    fn key(&self, index: usize) -> String {
+        Alphanumeric.sample_string(
+            &mut index_rng(self.seed, index as u64),
+            self.key_string_length,
+        )
+    }
+
+    /// Element `j` of the collection of the given key and its size in bytes. Elements are
+    /// numbered after the keys, so their generators do not overlap with the ones of the keys
+    fn element(&self, key: usize, j: usize) -> (CqlValue, usize) {
+        let domain = 2 * self.config.size;
+        let rng = &mut index_rng(self.seed, (self.total_keys + key * domain + j) as u64);
+        let size = self.element_sizes.sample(rng);
+        let value = match self.config.collection_type {
+            CollectionType::List | CollectionType::Set | CollectionType::Map => {
+                CqlValue::Text(Alphanumeric.sample_string(rng, size))
+            }
+            CollectionType::Udt | CollectionType::UdtColumn => {
+                let mut payload = vec![0u8; size];
+                rng.fill(&mut payload[..]);
+                CqlValue::UserDefinedType {
+                    keyspace: self.schema.keyspace.clone(),
+                    type_name: "item".to_string(),
+                    fields: vec![
+                        ("id".to_string(), Some(CqlValue::BigInt(j as i64))),
+                        ("payload".to_string(), Some(CqlValue::Blob(payload))),
+                    ],
+                }
+            }
+        };
+        (value, size)
+    }
+
+    /// Value an overwrite writes and its size in bytes: all `size` elements of a collection,
+    /// or element `j` of a UDT column
+    fn overwrite_value(&self, key: usize, j: usize) -> (CqlValue, usize) {
+        match self.config.collection_type {
+            CollectionType::UdtColumn => self.element(key, j),
+            _ => self.collection(key, 0..self.config.size),
+        }
+    }
+
+    /// `payload` field of element `j` of a UDT column and its size in bytes
+    fn payload(&self, key: usize, j: usize) -> (CqlValue, usize) {
+        let (value, size) = self.element(key, j);
+        let payload = match value {
+            CqlValue::UserDefinedType { fields, .. } => fields
+                .into_iter()
+                .find_map(|(name, value)| (name == "payload").then_some(value).flatten()),
+            _ => None,
+        };
+        (payload.unwrap_or(CqlValue::Blob(Vec::new())), size)
+    }
+
+    /// Collection of the given elements of a key and its size in bytes
+    fn collection(&self, key: usize, elements: impl Iterator<Item = usize>) -> (CqlValue, usize) {
+        let mut size = 0;
+        let mut values = Vec::new();
+        let mut entries = Vec::new();
+        for j in elements {
+            let (value, element_size) = self.element(key, j);
+            size += element_size;
+            match self.config.collection_type {
+                CollectionType::List | CollectionType::Set | CollectionType::UdtColumn => {
+                    values.push(value)
+                }
+                CollectionType::Map | CollectionType::Udt => {
+                    entries.push((CqlValue::Int(j as i32), value))
+                }
+            }
+        }
+        let collection = match self.config.collection_type {
+            CollectionType::List => CqlValue::List(values),
+            CollectionType::Set => CqlValue::Set(values),
+            CollectionType::Map | CollectionType::Udt => CqlValue::Map(entries),
+            CollectionType::UdtColumn => unreachable!("A UDT column holds a single element"),
+        };
+        (collection, size)
+    }
+
+    /// Value that removes element `j`: the element itself for lists and sets, its key for maps
+    fn removed(&self, key: usize, j: usize) -> CqlValue {
+        match self.config.collection_type {
+            CollectionType::List => CqlValue::List(vec![self.element(key, j).0]),
+            CollectionType::Set => CqlValue::Set(vec![self.element(key, j).0]),
+            CollectionType::Map | CollectionType::Udt => {
+                CqlValue::Set(vec![CqlValue::Int(j as i32)])
+            }
+            CollectionType::UdtColumn => unreachable!("Removes clear a field of a UDT column"),
+        }
+    }
+
+    /// Every collection type has its own table unless the table name is configured
+    fn default_table(&self) -> String {
+        let frozen = if self.config.frozen { "_frozen" } else { "" };
+        format!("collections_{}{}", self.config.collection_type, frozen)
+    }
+
+    fn table(&self) -> String {
+        self.schema.table(&self.default_table())
+    }
+
+    fn statements(&self) -> Result<&Statements> {
+        self.statements
+            .as_ref()
+            .ok_or_else(|| anyhow!("Schema is not set up, statements are not prepared"))
+    }
+}
+
+impl CollectionConfig {
+    fn column_type(&self) -> String {
+        let column = match self.collection_type {
+            CollectionType::List => "list<text>",
+            CollectionType::Set => "set<text>",
+            CollectionType::Map => "map<int, text>",
+            CollectionType::Udt => "map<int, frozen<item>>",
+            CollectionType::UdtColumn => "item",
+        };
+        if self.frozen {
+            format!("frozen<{}>", column)
+        } else {
+            column.to_string()
+        }
+    }
+}
+
+#[async_trait]
+impl Workload for CollectionWorkload {
+    async fn create_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        let create_type = format!(
+            "CREATE TYPE IF NOT EXISTS {} (id bigint, payload blob);",
+            self.schema.qualified("item")
+        );
+        let table = self.table();
+        let create_table = self.schema.create_table(
+            &table,
+            &format!("key text PRIMARY KEY, value {}", self.config.column_type()),
+            &[],
+        );
+        self.schema
+            .create(session, &[create_type, create_table])
+            .await
+    }
+
+    async fn prepare(&mut self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()> {
+        let table = self.table();
+        let schema = &self.schema;
+        let prepare = |cql, consistency| schema.prepare(session, cql, consistency);
+        let write = self.write_consistency;
+        let overwrite =
+            prepare(format!("UPDATE {table} SET value = ? WHERE key = ?"), write).await?;
+        let read = prepare(
+            format!("SELECT value FROM {table} WHERE key = ?"),
+            self.read_consistency,
+        )
+        .await?;
+        // frozen collections reject appends and removes, their weights are zeroed in `new`
+        // and the overwrite only fills their place
+        let (append, remove) = if self.config.frozen {
+            (overwrite.clone(), overwrite.clone())
+        } else if self.config.collection_type == CollectionType::UdtColumn {
+            (
+                prepare(
+                    format!("UPDATE {table} SET value.payload = ? WHERE key = ?"),
+                    write,
+                )
+                .await?,
+                prepare(
+                    format!("UPDATE {table} SET value.payload = null WHERE key = ?"),
+                    write,
+                )
+                .await?,
+            )
+        } else {
+            (
+                prepare(
+                    format!("UPDATE {table} SET value = value + ? WHERE key = ?"),
+                    write,
+                )
+                .await?,
+                prepare(
+                    format!("UPDATE {table} SET value = value - ? WHERE key = ?"),
+                    write,
+                )
+                .await?,
+            )
+        };
+        self.statements = Some(Statements {
+            append,
+            remove,
+            overwrite,
+            read,
+        });
+        Ok(())
+    }
+
+    fn set_slice(&mut self, slice: KeySlice) {
+        self.read_keys = self.read_keys.slice(slice);
+        self.write_keys = self.write_keys.slice(slice);
+    }
+
+    fn population_size(&self) -> usize {
+        self.total_keys
+    }
+
+    async fn populate(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        index: usize,
+    ) -> Result<()> {
+        let (value, _) = self.overwrite_value(index, 0);
+        session
+            .execute_unpaged(&self.statements()?.overwrite, (value, self.key(index)))
+            .await?;
+        Ok(())
+    }
+
+    async fn next_operation(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<Operation> {
+        let statements = self.statements()?;
+        let (query_type, index, j) = {
+            let mut rng = rand::thread_rng();
+            let query_type = OPERATIONS[self.operations.sample(&mut rng)];
+            let index = if query_type == QueryType::Read {
+                self.read_keys.sample(&mut rng)
+            } else {
+                self.write_keys.sample(&mut rng)
+            };
+            (query_type, index, rng.gen_range(0..2 * self.config.size))
+        };
+        let key = self.key(index);
+        let udt_column = self.config.collection_type == CollectionType::UdtColumn;
+        let operation = match query_type {
+            QueryType::Append if udt_column => {
+                let (payload, size) = self.payload(index, j);
+                session
+                    .execute_unpaged(&statements.append, (payload, &key))
+                    .await?;
+                Operation::new(QueryType::FieldUpdate).with_value_size(size)
+            }
+            QueryType::Remove if udt_column => {
+                session.execute_unpaged(&statements.remove, (&key,)).await?;
+                Operation::new(query_type)
+            }
+            QueryType::Append => {
+                let (collection, size) = self.collection(index, j..j + 1);
+                session
+                    .execute_unpaged(&statements.append, (collection, &key))
+                    .await?;
+                Operation::new(query_type).with_value_size(size)
+            }
+            QueryType::Remove => {
+                session
+                    .execute_unpaged(&statements.remove, (self.removed(index, j), &key))
+                    .await?;
+                Operation::new(query_type)
+            }
+            QueryType::Overwrite => {
+                let (value, size) = self.overwrite_value(index, j);
+                session
+                    .execute_unpaged(&statements.overwrite, (value, &key))
+                    .await?;
+                Operation::new(query_type).with_value_size(size)
+            }
+            _ => {
+                session.execute_unpaged(&statements.read, (&key,)).await?;
+                return Ok(
+                    Operation::new(query_type).with_consistency(self.read_consistency.consistency)
+                );
+            }
+        };
+        Ok(operation.with_consistency(self.write_consistency.consistency))
+    }
+
+    /// Dropping only the configured table drops the type after it, the keyspace takes the
+    /// type with it otherwise
+    async fn drop_schema(&self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()> {
+        session
+            .query_unpaged(self.schema.drop_schema(&self.default_table()), &[])
+            .await?;
+        if self.schema.table.is_some() {
+            let drop_type = format!("DROP TYPE IF EXISTS {}", self.schema.qualified("item"));
+            session.query_unpaged(drop_type, &[]).await?;
+        }
+        Ok(())
+    }
+
+    async fn truncate_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        session
+            .query_unpaged(format!("TRUNCATE {}", self.table()), &[])
+            .await?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for CollectionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CollectionType::List => write!(f, "list"),
+            CollectionType::Set => write!(f, "set"),
+            CollectionType::Map => write!(f, "map"),
+            CollectionType::Udt => write!(f, "udt"),
+            CollectionType::UdtColumn => write!(f, "udt_column"),
+        }
+    }
+}
+
+impl FromStr for CollectionMix {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let weights = parse_weights(s, &["append", "remove", "overwrite", "read"])?;
+        let [append, remove, overwrite, read] = weights[..] else {
+            unreachable!()
+        };
+        Ok(CollectionMix {
+            append,
+            remove,
+            overwrite,
+            read,
+        })
+    }
+}
+
+impl fmt::Display for CollectionMix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "append: {}, remove: {}, overwrite: {}, read: {}",
+            self.append, self.remove, self.overwrite, self.read
+        )
+    }
+}
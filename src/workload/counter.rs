@@ -0,0 +1,325 @@
+use crate::consistency::StatementConsistency;
+use crate::distribution::{KeyDistribution, KeySampler, KeySlice};
+use crate::generator::index_rng;
+use crate::reporter::{Operation, QueryType};
+use crate::schema::SchemaConfig;
+use crate::workload::{Verification, Workload};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use rand::distributions::{Alphanumeric, DistString};
+use rand::{random, Rng};
+use scylla::batch::{Batch, BatchType};
+use scylla::frame::value::Counter;
+use scylla::prepared_statement::PreparedStatement;
+use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
+use std::sync::atomic::{AtomicI64, Ordering};
+
+/// Default name of the table
+const TABLE: &str = "counters";
+
+/// Largest step of a single increment or decrement
+const MAX_DELTA: i64 = 10;
+
+/// Single-row partitions with a text key and a counter, incremented, decremented and read
+/// by key. The client tracks the total it expects for every key, and once the benchmark is
+/// over every counter is read back and compared with the value it had before the benchmark
+/// plus that total, so counters left over by earlier runs do not fail the verification.
+pub struct CounterWorkload {
+    reads_percentage: f32,
+    total_keys: usize,
+    key_string_length: usize,
+    seed: u64,
+    read_consistency: StatementConsistency,
+    write_consistency: StatementConsistency,
+    read_keys: KeySampler,
+    write_keys: KeySampler,
+    totals: Vec<ExpectedTotal>,
+    prepared_read: Option<PreparedStatement>,
+    prepared_update: Option<PreparedStatement>,
+    /// Number of updates in a counter batch, updates are sent one by one if not set
+    batch_size: Option<usize>,
+    prepared_batch: Option<Batch>,
+    schema: SchemaConfig,
+}
+
+#[derive(Default)]
+struct ExpectedTotal {
+    /// Value of the counter before the benchmark, see `Workload::read_initial`
+    initial: AtomicI64,
+    /// Sum of the acknowledged updates
+    value: AtomicI64,
+    /// Sum of the absolute deltas of failed updates. A failed counter update may still
+    /// be applied, so the server value may be off by up to this much
+    uncertain: AtomicI64,
+}
+
+impl CounterWorkload {
+    pub fn new(
+        total_keys: usize,
+        key_string_length: usize,
+        reads_percentage: f32,
+        read_distribution: &KeyDistribution,
+        write_distribution: &KeyDistribution,
+        seed: u64,
+    ) -> CounterWorkload {
+        println!("Using {total_keys} counters, key length: {key_string_length}, seed: {seed}");
+        CounterWorkload {
+            reads_percentage,
+            total_keys,
+            key_string_length,
+            seed,
+            read_consistency: StatementConsistency::default(),
+            write_consistency: StatementConsistency::default(),
+            read_keys: read_distribution.sampler(total_keys),
+            write_keys: write_distribution.sampler(total_keys),
+            totals: (0..total_keys).map(|_| ExpectedTotal::default()).collect(),
+            prepared_read: None,
+            prepared_update: None,
+            batch_size: None,
+            prepared_batch: None,
+            schema: SchemaConfig::default(),
+        }
+    }
+
+    /// Consistency settings of the read and the update statement
+    pub fn with_consistency(
+        mut self,
+        read: StatementConsistency,
+        write: StatementConsistency,
+    ) -> CounterWorkload {
+        self.read_consistency = read;
+        self.write_consistency = write;
+        self
+    }
+
+    /// Keyspace, table name and table options
+    pub fn with_schema(mut self, schema: SchemaConfig) -> CounterWorkload {
+        self.schema = schema;
+        self
+    }
+
+    /// Send updates in counter batches of `size` updates of random keys
+    pub fn with_batches(mut self, size: usize) -> CounterWorkload {
+        self.batch_size = Some(size);
+        self
+    }
+
+    /// Key of the counter with the given index, the same index always gives the same key
+    fn key(&self, index: usize) -> String {
+        Alphanumeric.sample_string(
+            &mut index_rng(self.seed, index as u64),
+            self.key_string_length,
+        )
+    }
+
+    fn statements(&self) -> Result<(&PreparedStatement, &PreparedStatement)> {
+        match (&self.prepared_read, &self.prepared_update) {
+            (Some(read), Some(update)) => Ok((read, update)),
+            _ => Err(anyhow!("Schema is not set up, statements are not prepared")),
+        }
+    }
+
+    async fn read(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        index: usize,
+    ) -> Result<Option<i64>> {
+        let (read, _) = self.statements()?;
+        let row = session
+            .execute_unpaged(read, (self.key(index),))
+            .await?
+            .into_rows_result()?
+            .maybe_first_row::<(Counter,)>()?;
+        Ok(row.map(|(Counter(value),)| value))
+    }
+
+    /// Random key index and a non-zero delta of an update
+    fn next_update(&self) -> (usize, i64) {
+        let mut rng = rand::thread_rng();
+        let delta = rng.gen_range(1..=MAX_DELTA);
+        let delta = if rng.gen::<bool>() { delta } else { -delta };
+        (self.write_keys.sample(&mut rng), delta)
+    }
+
+    /// Send a counter batch of updates. The totals are only changed once the batch is
+    /// acknowledged, a failed batch makes every delta of it uncertain
+    async fn update_batch(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        batch: &Batch,
+    ) -> Result<Operation> {
+        let updates: Vec<_> = (0..batch.statements.len())
+            .map(|_| self.next_update())
+            .collect();
+        let values: Vec<_> = updates
+            .iter()
+            .map(|(index, delta)| (Counter(*delta), self.key(*index)))
+            .collect();
+        let result = session.batch(batch, &values).await;
+        for (index, delta) in &updates {
+            let total = &self.totals[*index];
+            if result.is_ok() {
+                total.value.fetch_add(*delta, Ordering::Relaxed);
+            } else {
+                total.uncertain.fetch_add(delta.abs(), Ordering::Relaxed);
+            }
+        }
+        result?;
+        Ok(Operation::new(QueryType::Batch)
+            .with_rows(updates.len())
+            .with_consistency(self.write_consistency.consistency))
+    }
+}
+
+#[async_trait]
+impl Workload for CounterWorkload {
+    async fn create_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        let table = self.schema.table(TABLE);
+        let create_table =
+            self.schema
+                .create_table(&table, "key text PRIMARY KEY, value counter", &[]);
+        self.schema.create(session, &[create_table]).await
+    }
+
+    async fn prepare(&mut self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()> {
+        let table = self.schema.table(TABLE);
+        let read = self
+            .schema
+            .prepare(
+                session,
+                format!("SELECT value FROM {table} WHERE key = ?"),
+                self.read_consistency,
+            )
+            .await?;
+        self.prepared_read = Some(read);
+        let update = self
+            .schema
+            .prepare(
+                session,
+                format!("UPDATE {table} SET value = value + ? WHERE key = ?"),
+                self.write_consistency,
+            )
+            .await?;
+        if let Some(size) = self.batch_size {
+            let mut batch = Batch::new(BatchType::Counter);
+            for _ in 0..size {
+                batch.append_statement(update.clone());
+            }
+            if let Some(consistency) = self.write_consistency.consistency {
+                batch.set_consistency(consistency.0);
+            }
+            self.prepared_batch = Some(batch);
+        }
+        self.prepared_update = Some(update);
+        Ok(())
+    }
+
+    fn set_slice(&mut self, slice: KeySlice) {
+        self.read_keys = self.read_keys.slice(slice);
+        self.write_keys = self.write_keys.slice(slice);
+    }
+
+    fn population_size(&self) -> usize {
+        self.total_keys
+    }
+
+    /// Create the counter row by adding zero, so reads of untouched keys find a row
+    async fn populate(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        index: usize,
+    ) -> Result<()> {
+        let (_, update) = self.statements()?;
+        session
+            .execute_unpaged(update, (Counter(0), self.key(index)))
+            .await?;
+        Ok(())
+    }
+
+    async fn next_operation(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<Operation> {
+        let (_, update) = self.statements()?;
+        if random::<f32>() < self.reads_percentage {
+            let index = self.read_keys.sample(&mut rand::thread_rng());
+            self.read(session, index).await?;
+            return Ok(
+                Operation::new(QueryType::Read).with_consistency(self.read_consistency.consistency)
+            );
+        }
+        if let Some(batch) = &self.prepared_batch {
+            return self.update_batch(session, batch).await;
+        }
+        let (index, delta) = self.next_update();
+        let total = &self.totals[index];
+        match session
+            .execute_unpaged(update, (Counter(delta), self.key(index)))
+            .await
+        {
+            Ok(_) => total.value.fetch_add(delta, Ordering::Relaxed),
+            Err(e) => {
+                total.uncertain.fetch_add(delta.abs(), Ordering::Relaxed);
+                return Err(e.into());
+            }
+        };
+        let query_type = if delta > 0 {
+            QueryType::Increment
+        } else {
+            QueryType::Decrement
+        };
+        Ok(Operation::new(query_type).with_consistency(self.write_consistency.consistency))
+    }
+
+    async fn read_initial(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        index: usize,
+    ) -> Result<()> {
+        let initial = self.read(session, index).await?.unwrap_or(0);
+        self.totals[index].initial.store(initial, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Compare the counter with its initial value plus the total of the updates sent by
+    /// this process
+    async fn verify(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        index: usize,
+    ) -> Result<Verification> {
+        let total = &self.totals[index];
+        let expected = total.initial.load(Ordering::Relaxed) + total.value.load(Ordering::Relaxed);
+        let uncertain = total.uncertain.load(Ordering::Relaxed);
+        Ok(match self.read(session, index).await? {
+            None if expected == 0 => Verification::Ok,
+            None => Verification::Missing,
+            Some(value) if (value - expected).abs() <= uncertain => Verification::Ok,
+            Some(_) => Verification::Mismatch,
+        })
+    }
+
+    fn verify_after_run(&self) -> bool {
+        true
+    }
+
+    async fn drop_schema(&self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()> {
+        session
+            .query_unpaged(self.schema.drop_schema(TABLE), &[])
+            .await?;
+        Ok(())
+    }
+
+    async fn truncate_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        session
+            .query_unpaged(format!("TRUNCATE {}", self.schema.table(TABLE)), &[])
+            .await?;
+        Ok(())
+    }
+}
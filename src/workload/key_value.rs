@@ -0,0 +1,406 @@
+use crate::consistency::StatementConsistency;
+use crate::distribution::{KeyDistribution, KeySampler, KeySlice, ValueSizeDistribution};
+use crate::generator::index_rng;
+use crate::reporter::{Operation, QueryType};
+use crate::schema::SchemaConfig;
+use crate::workload::{Verification, Workload};
+use crate::write_options::{Using, WriteOptions, USING_CLAUSE};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use rand::distributions::{Alphanumeric, DistString};
+use rand::{random, Rng};
+use scylla::batch::{Batch, BatchType};
+use scylla::frame::value::MaybeUnset;
+use scylla::prepared_statement::PreparedStatement;
+use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
+
+/// Single-row partitions with a text key and a blob value, read and written by key.
+/// Keys and values are never stored, the pair with a given index is regenerated
+/// from the seed whenever it is needed, so memory use does not depend on the key count.
+pub struct KeyValueWorkload {
+    reads_percentage: f32,
+    total_keys: usize,
+    key_string_length: usize,
+    value_sizes: ValueSizeDistribution,
+    seed: u64,
+    verify_reads: bool,
+    read_consistency: StatementConsistency,
+    write_consistency: StatementConsistency,
+    read_keys: KeySampler,
+    write_keys: KeySampler,
+    prepared_read: Option<PreparedStatement>,
+    prepared_write: Option<PreparedStatement>,
+    batches: Option<BatchConfig>,
+    prepared_batch: Option<Batch>,
+    write_options: WriteOptions,
+    schema: SchemaConfig,
+}
+
+pub struct KeyValue(String, Vec<u8>);
+
+/// How writes are grouped into batches
+#[derive(Clone, Copy)]
+pub struct BatchConfig {
+    /// Number of rows in a batch
+    pub size: usize,
+    pub batch_type: BatchType,
+    /// Only put rows owned by the same replica shard into a batch
+    pub group_by_token: bool,
+}
+
+/// Default name of the table
+const TABLE: &str = "test";
+
+/// A token-grouped batch is sent with fewer rows if no more rows owned by the same
+/// replica are found in this many tries per row
+const GROUPING_TRIES_PER_ROW: usize = 100;
+
+impl KeyValueWorkload {
+    pub fn new(
+        total_keys: usize,
+        key_string_length: usize,
+        value_sizes: &ValueSizeDistribution,
+        reads_percentage: f32,
+        read_distribution: &KeyDistribution,
+        write_distribution: &KeyDistribution,
+        seed: u64,
+    ) -> KeyValueWorkload {
+        println!(
+            "Using {total_keys} key-value pairs, key length: {key_string_length}, value size: {value_sizes}, seed: {seed}"
+        );
+        KeyValueWorkload {
+            reads_percentage,
+            total_keys,
+            key_string_length,
+            value_sizes: value_sizes.clone(),
+            seed,
+            verify_reads: false,
+            read_consistency: StatementConsistency::default(),
+            write_consistency: StatementConsistency::default(),
+            read_keys: read_distribution.sampler(total_keys),
+            write_keys: write_distribution.sampler(total_keys),
+            prepared_read: None,
+            prepared_write: None,
+            batches: None,
+            prepared_batch: None,
+            write_options: WriteOptions::default(),
+            schema: SchemaConfig::default(),
+        }
+    }
+
+    /// Compare the value of every read with the expected one
+    pub fn with_verified_reads(mut self) -> KeyValueWorkload {
+        self.verify_reads = true;
+        self
+    }
+
+    /// Consistency settings of the read and the write statement
+    pub fn with_consistency(
+        mut self,
+        read: StatementConsistency,
+        write: StatementConsistency,
+    ) -> KeyValueWorkload {
+        self.read_consistency = read;
+        self.write_consistency = write;
+        self
+    }
+
+    /// Keyspace, table name and table options
+    pub fn with_schema(mut self, schema: SchemaConfig) -> KeyValueWorkload {
+        self.schema = schema;
+        self
+    }
+
+    /// TTL and timestamps of the writes
+    pub fn with_write_options(mut self, write_options: WriteOptions) -> KeyValueWorkload {
+        self.write_options = write_options;
+        self
+    }
+
+    /// Send writes in batches of the given configuration instead of one by one
+    pub fn with_batches(mut self, batches: BatchConfig) -> KeyValueWorkload {
+        self.batches = Some(batches);
+        self
+    }
+
+    /// Key of the pair with the given index, without generating the value
+    fn key(&self, index: usize) -> String {
+        let rng = &mut index_rng(self.seed, index as u64);
+        Alphanumeric.sample_string(rng, self.key_string_length)
+    }
+
+    /// Key and value with the given index, the same index always gives the same pair.
+    /// The key is drawn first, so it is the same as `key(index)`
+    fn key_value(&self, index: usize) -> KeyValue {
+        let rng = &mut index_rng(self.seed, index as u64);
+        let key = Alphanumeric.sample_string(rng, self.key_string_length);
+        let mut value = vec![0u8; self.value_sizes.sample(rng)];
+        rng.fill(&mut value[..]);
+        KeyValue(key, value)
+    }
+
+    /// Read the row with the given index and compare it with the expected value, also
+    /// returns the expected value size
+    async fn verify_key(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        index: usize,
+    ) -> Result<(Verification, usize)> {
+        let (read, _) = self.statements()?;
+        let expected = self.key_value(index);
+        let row = session
+            .execute_unpaged(read, (&expected.0,))
+            .await?
+            .into_rows_result()?
+            .maybe_first_row::<(String, Vec<u8>)>()?;
+        let verification = match row {
+            None => Verification::Missing,
+            Some((_, value)) if value == expected.1 => Verification::Ok,
+            Some(_) => Verification::Mismatch,
+        };
+        Ok((verification, expected.1.len()))
+    }
+
+    /// Indexes of the rows of the next batch. With token grouping the rows are owned by the
+    /// replica shard of the first row, so the coordinator can apply the batch locally
+    fn batch_indexes(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        write: &PreparedStatement,
+        config: &BatchConfig,
+    ) -> Result<Vec<usize>> {
+        let mut rng = rand::thread_rng();
+        if !config.group_by_token {
+            return Ok((0..config.size)
+                .map(|_| self.write_keys.sample(&mut rng))
+                .collect());
+        }
+        let cluster = session.get_cluster_data();
+        let replica = |index: usize| -> Result<_> {
+            // the token only depends on the key, the other values do not need to be generated
+            let key = self.key(index);
+            let values = (
+                &key,
+                Vec::<u8>::new(),
+                MaybeUnset::<i32>::Unset,
+                MaybeUnset::<i64>::Unset,
+            );
+            let token = write
+                .calculate_token(&values)?
+                .ok_or_else(|| anyhow!("Could not compute the token of key {}", key))?;
+            Ok(cluster
+                .get_token_endpoints(&self.schema.keyspace, self.schema.table_name(TABLE), token)
+                .first()
+                .map(|(node, shard)| (node.host_id, *shard)))
+        };
+        let first = self.write_keys.sample(&mut rng);
+        let owner = replica(first)?;
+        let mut indexes = vec![first];
+        for _ in 0..config.size * GROUPING_TRIES_PER_ROW {
+            if indexes.len() == config.size {
+                break;
+            }
+            let index = self.write_keys.sample(&mut rng);
+            if replica(index)? == owner {
+                indexes.push(index);
+            }
+        }
+        Ok(indexes)
+    }
+
+    /// Batch of `rows` writes with the consistency of the write statement
+    fn new_batch(&self, write: &PreparedStatement, config: &BatchConfig, rows: usize) -> Batch {
+        let mut batch = Batch::new(config.batch_type);
+        for _ in 0..rows {
+            batch.append_statement(write.clone());
+        }
+        if let Some(consistency) = self.write_consistency.consistency {
+            batch.set_consistency(consistency.0);
+        }
+        if let Some(serial) = self.write_consistency.serial_consistency {
+            batch.set_serial_consistency(Some(serial.0));
+        }
+        batch
+    }
+
+    async fn write_batch(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<Operation> {
+        let (_, write) = self.statements()?;
+        let (Some(config), Some(batch)) = (&self.batches, &self.prepared_batch) else {
+            return Err(anyhow!("Schema is not set up, batch is not prepared"));
+        };
+        let indexes = self.batch_indexes(session, write, config)?;
+        let values: Vec<_> = indexes
+            .iter()
+            .map(|index| {
+                let KeyValue(key, value) = self.key_value(*index);
+                let Using { ttl, timestamp } = self.write_options.next();
+                (key, value, ttl, timestamp)
+            })
+            .collect();
+        let value_size = values.iter().map(|(_, value, _, _)| value.len()).sum();
+        if values.len() == config.size {
+            session.batch(batch, &values).await?;
+        } else {
+            // token grouping found fewer rows than the batch has statements
+            let smaller = self.new_batch(write, config, values.len());
+            session.batch(&smaller, &values).await?;
+        }
+        Ok(Operation::new(QueryType::Batch)
+            .with_value_size(value_size)
+            .with_rows(values.len())
+            .with_consistency(self.write_consistency.consistency))
+    }
+
+    fn statements(&self) -> Result<(&PreparedStatement, &PreparedStatement)> {
+        match (&self.prepared_read, &self.prepared_write) {
+            (Some(read), Some(write)) => Ok((read, write)),
+            _ => Err(anyhow!("Schema is not set up, statements are not prepared")),
+        }
+    }
+}
+
+#[async_trait]
+impl Workload for KeyValueWorkload {
+    async fn create_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        let table = self.schema.table(TABLE);
+        let create_table =
+            self.schema
+                .create_table(&table, "key text PRIMARY KEY, value blob", &[]);
+        self.schema.create(session, &[create_table]).await
+    }
+
+    async fn prepare(&mut self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()> {
+        let table = self.schema.table(TABLE);
+        let read = self
+            .schema
+            .prepare(
+                session,
+                format!("SELECT * FROM {table} WHERE key = ?"),
+                self.read_consistency,
+            )
+            .await?;
+        self.prepared_read = Some(read);
+        let write = self
+            .schema
+            .prepare(
+                session,
+                format!("INSERT INTO {table} (key, value) VALUES (?, ?) {USING_CLAUSE}"),
+                self.write_consistency,
+            )
+            .await?;
+        if let Some(config) = self.batches {
+            self.prepared_batch = Some(self.new_batch(&write, &config, config.size));
+        }
+        self.prepared_write = Some(write);
+        Ok(())
+    }
+
+    fn set_slice(&mut self, slice: KeySlice) {
+        self.read_keys = self.read_keys.slice(slice);
+        self.write_keys = self.write_keys.slice(slice);
+    }
+
+    fn population_size(&self) -> usize {
+        self.total_keys
+    }
+
+    async fn populate(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        index: usize,
+    ) -> Result<()> {
+        let (_, write) = self.statements()?;
+        perform_write(
+            session,
+            write,
+            &self.key_value(index),
+            self.write_options.next_population(),
+        )
+        .await
+    }
+
+    async fn next_operation(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<Operation> {
+        let (read, write) = self.statements()?;
+        if random::<f32>() < self.reads_percentage {
+            let index = self.read_keys.sample(&mut rand::thread_rng());
+            if self.verify_reads {
+                let (verification, value_size) = self.verify_key(session, index).await?;
+                return Ok(Operation::new(QueryType::Read)
+                    .with_value_size(value_size)
+                    .with_consistency(self.read_consistency.consistency)
+                    .with_verification(verification));
+            }
+            let kv = self.key_value(index);
+            perform_read(session, read, &kv).await?;
+            Ok(Operation::new(QueryType::Read)
+                .with_value_size(kv.1.len())
+                .with_consistency(self.read_consistency.consistency))
+        } else if self.prepared_batch.is_some() {
+            self.write_batch(session).await
+        } else {
+            let kv = self.key_value(self.write_keys.sample(&mut rand::thread_rng()));
+            perform_write(session, write, &kv, self.write_options.next()).await?;
+            Ok(Operation::new(QueryType::Write)
+                .with_value_size(kv.1.len())
+                .with_consistency(self.write_consistency.consistency))
+        }
+    }
+
+    async fn drop_schema(&self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()> {
+        session
+            .query_unpaged(self.schema.drop_schema(TABLE), &[])
+            .await?;
+        Ok(())
+    }
+
+    async fn verify(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        index: usize,
+    ) -> Result<Verification> {
+        Ok(self.verify_key(session, index).await?.0)
+    }
+
+    async fn truncate_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        session
+            .query_unpaged(format!("TRUNCATE {}", self.schema.table(TABLE)), &[])
+            .await?;
+        Ok(())
+    }
+}
+
+async fn perform_read(
+    session: &GenericSession<CurrentDeserializationApi>,
+    ps: &PreparedStatement,
+    kv: &KeyValue,
+) -> Result<()> {
+    session
+        .execute_unpaged(ps, (&kv.0,))
+        .await
+        .context("Row not found")?;
+    Ok(())
+}
+
+async fn perform_write(
+    session: &GenericSession<CurrentDeserializationApi>,
+    ps: &PreparedStatement,
+    kv: &KeyValue,
+    using: Using,
+) -> Result<()> {
+    session
+        .execute_unpaged(ps, (&kv.0, &kv.1, using.ttl, using.timestamp))
+        .await?;
+    Ok(())
+}
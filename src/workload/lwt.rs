@@ -0,0 +1,269 @@
+use crate::consistency::StatementConsistency;
+use crate::distribution::KeySlice;
+use crate::generator::index_rng;
+use crate::reporter::{Operation, QueryType};
+use crate::schema::SchemaConfig;
+use crate::workload::Workload;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use rand::distributions::{Alphanumeric, DistString};
+use rand::random;
+use scylla::frame::response::result::{CqlValue, Row};
+use scylla::prepared_statement::PreparedStatement;
+use scylla::serialize::row::SerializeRow;
+use scylla::transport::errors::{DbError, QueryError};
+use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
+use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+
+/// Default name of the table
+const TABLE: &str = "lwt";
+
+/// Compare-and-set operations on single-row partitions with a text key and a bigint value.
+/// Every key starts at 0, `CasUpdate` increments it with `UPDATE ... IF value = ?` using
+/// the last value seen for the key, `CasInsert` tries `INSERT ... IF NOT EXISTS`.
+///
+/// `contention` consecutive operations go to the same key, so with enough concurrency that
+/// many clients fight over each key at any moment.
+pub struct LwtWorkload {
+    total_keys: usize,
+    key_string_length: usize,
+    seed: u64,
+    contention: usize,
+    inserts_percentage: f32,
+    consistency: StatementConsistency,
+    next_operation: AtomicUsize,
+    /// Keys of this executor when several executors share the dataset
+    slice: KeySlice,
+    /// Last value seen for every key, the expected value of the next compare-and-set.
+    /// Values written by other clients are learned from the rows of not applied updates
+    known_values: Vec<AtomicI64>,
+    prepared_populate: Option<PreparedStatement>,
+    prepared_insert: Option<PreparedStatement>,
+    prepared_update: Option<PreparedStatement>,
+    schema: SchemaConfig,
+}
+
+struct Statements<'a> {
+    populate: &'a PreparedStatement,
+    insert: &'a PreparedStatement,
+    update: &'a PreparedStatement,
+}
+
+impl LwtWorkload {
+    pub fn new(
+        total_keys: usize,
+        key_string_length: usize,
+        contention: usize,
+        inserts_percentage: f32,
+        seed: u64,
+    ) -> LwtWorkload {
+        println!(
+            "Using {total_keys} LWT keys, key length: {key_string_length}, contention: {contention}, seed: {seed}"
+        );
+        LwtWorkload {
+            total_keys,
+            key_string_length,
+            seed,
+            contention,
+            inserts_percentage,
+            consistency: StatementConsistency::default(),
+            next_operation: AtomicUsize::new(0),
+            slice: KeySlice::default(),
+            known_values: (0..total_keys).map(|_| AtomicI64::new(0)).collect(),
+            prepared_populate: None,
+            prepared_insert: None,
+            prepared_update: None,
+            schema: SchemaConfig::default(),
+        }
+    }
+
+    /// Consistency settings of the conditional statements
+    pub fn with_consistency(mut self, consistency: StatementConsistency) -> LwtWorkload {
+        self.consistency = consistency;
+        self
+    }
+
+    /// Keyspace, table name and table options
+    pub fn with_schema(mut self, schema: SchemaConfig) -> LwtWorkload {
+        self.schema = schema;
+        self
+    }
+
+    /// Key with the given index, the same index always gives the same key
+    fn key(&self, index: usize) -> String {
+        Alphanumeric.sample_string(
+            &mut index_rng(self.seed, index as u64),
+            self.key_string_length,
+        )
+    }
+
+    fn statements(&self) -> Result<Statements<'_>> {
+        match (
+            &self.prepared_populate,
+            &self.prepared_insert,
+            &self.prepared_update,
+        ) {
+            (Some(populate), Some(insert), Some(update)) => Ok(Statements {
+                populate,
+                insert,
+                update,
+            }),
+            _ => Err(anyhow!("Schema is not set up, statements are not prepared")),
+        }
+    }
+
+    /// Execute a conditional statement and return its `[applied]` column and the current
+    /// value of the row if the condition failed, `None` if the statement timed out
+    async fn execute_conditional(
+        session: &GenericSession<CurrentDeserializationApi>,
+        statement: &PreparedStatement,
+        values: impl SerializeRow,
+    ) -> Result<Option<(bool, Option<i64>)>> {
+        let result = match session.execute_unpaged(statement, values).await {
+            Ok(result) => result,
+            Err(e) if is_timeout(&e) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let row = result
+            .into_rows_result()?
+            .maybe_first_row::<Row>()?
+            .ok_or_else(|| anyhow!("Conditional statement returned no rows"))?;
+        let applied = matches!(row.columns.first(), Some(Some(CqlValue::Boolean(true))));
+        let current = row.columns.iter().skip(1).find_map(|column| match column {
+            Some(CqlValue::BigInt(value)) => Some(*value),
+            _ => None,
+        });
+        Ok(Some((applied, current)))
+    }
+}
+
+/// Timeouts are an expected outcome under contention, Paxos rounds that do not finish
+/// in time end with a CAS write timeout
+fn is_timeout(error: &QueryError) -> bool {
+    matches!(
+        error,
+        QueryError::RequestTimeout(_)
+            | QueryError::TimeoutError
+            | QueryError::DbError(
+                DbError::WriteTimeout { .. } | DbError::ReadTimeout { .. },
+                _
+            )
+    )
+}
+
+#[async_trait]
+impl Workload for LwtWorkload {
+    async fn create_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        let table = self.schema.table(TABLE);
+        let create_table =
+            self.schema
+                .create_table(&table, "key text PRIMARY KEY, value bigint", &[]);
+        self.schema.create(session, &[create_table]).await
+    }
+
+    async fn prepare(&mut self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()> {
+        let table = self.schema.table(TABLE);
+        self.prepared_populate = Some(
+            session
+                .prepare(format!("INSERT INTO {table} (key, value) VALUES (?, 0)"))
+                .await?,
+        );
+        let insert = self
+            .schema
+            .prepare(
+                session,
+                format!("INSERT INTO {table} (key, value) VALUES (?, 0) IF NOT EXISTS"),
+                self.consistency,
+            )
+            .await?;
+        self.prepared_insert = Some(insert);
+        let update = self
+            .schema
+            .prepare(
+                session,
+                format!("UPDATE {table} SET value = ? WHERE key = ? IF value = ?"),
+                self.consistency,
+            )
+            .await?;
+        self.prepared_update = Some(update);
+        Ok(())
+    }
+
+    fn set_slice(&mut self, slice: KeySlice) {
+        self.slice = slice;
+    }
+
+    fn population_size(&self) -> usize {
+        self.total_keys
+    }
+
+    async fn populate(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        index: usize,
+    ) -> Result<()> {
+        let statements = self.statements()?;
+        session
+            .execute_unpaged(statements.populate, (self.key(index),))
+            .await?;
+        Ok(())
+    }
+
+    async fn next_operation(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<Operation> {
+        let statements = self.statements()?;
+        let keys = self.slice.range(self.total_keys);
+        let index = keys.start
+            + self.next_operation.fetch_add(1, Ordering::Relaxed) / self.contention % keys.len();
+        let key = self.key(index);
+        if random::<f32>() < self.inserts_percentage {
+            let operation = Operation::new(QueryType::CasInsert);
+            let outcome = Self::execute_conditional(session, statements.insert, (&key,)).await?;
+            return Ok(match outcome {
+                Some((applied, _)) => operation.with_applied(applied),
+                None => operation.with_timeout(),
+            });
+        }
+        let operation = Operation::new(QueryType::CasUpdate);
+        let known_value = &self.known_values[index];
+        let expected = known_value.load(Ordering::Relaxed);
+        let outcome =
+            Self::execute_conditional(session, statements.update, (expected + 1, &key, expected))
+                .await?;
+        Ok(match outcome {
+            Some((true, _)) => {
+                known_value.fetch_max(expected + 1, Ordering::Relaxed);
+                operation.with_applied(true)
+            }
+            Some((false, current)) => {
+                if let Some(current) = current {
+                    known_value.store(current, Ordering::Relaxed);
+                }
+                operation.with_applied(false)
+            }
+            None => operation.with_timeout(),
+        })
+    }
+
+    async fn drop_schema(&self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()> {
+        session
+            .query_unpaged(self.schema.drop_schema(TABLE), &[])
+            .await?;
+        Ok(())
+    }
+
+    async fn truncate_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        session
+            .query_unpaged(format!("TRUNCATE {}", self.schema.table(TABLE)), &[])
+            .await?;
+        Ok(())
+    }
+}
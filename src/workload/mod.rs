@@ -0,0 +1,166 @@
+mod collection;
+mod counter;
+mod key_value;
+mod lwt;
+mod profile;
+mod replay;
+mod time_series;
+mod wide;
+
+pub use collection::{CollectionConfig, CollectionMix, CollectionType, CollectionWorkload};
+pub use counter::CounterWorkload;
+pub use key_value::{BatchConfig, KeyValueWorkload};
+pub use lwt::LwtWorkload;
+pub use profile::ProfileWorkload;
+pub use replay::ReplayWorkload;
+pub use time_series::{CompactionWindow, TimeSeriesConfig, TimeSeriesReadMix, TimeSeriesWorkload};
+pub use wide::{WideConfig, WideDeleteMix, WideReadMix, WideWorkload};
+
+use crate::distribution::KeySlice;
+use crate::reporter::Operation;
+use crate::trace::Trace;
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
+
+/// Outcome of reading a row back and comparing it with the value the workload wrote
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verification {
+    Ok,
+    Missing,
+    Mismatch,
+}
+
+/// Tombstone threshold a read ran into
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TombstoneAlert {
+    /// The response carried a warning about the number of tombstones read
+    Warning,
+    /// The read failed, as it does once a replica reads more tombstones than allowed
+    Failure,
+}
+
+/// A workload describes what is stored in the database and which queries are sent to it.
+/// The executor only decides when and how many operations are run, the workload decides
+/// what each of them does.
+#[async_trait]
+pub trait Workload: Send + Sync {
+    /// Create the keyspace and tables used by the workload
+    async fn create_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()>;
+
+    /// Prepare the statements of the workload, the schema must already exist
+    async fn prepare(&mut self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()>;
+
+    /// Create the schema and prepare the statements
+    async fn setup_schema(
+        &mut self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        self.create_schema(session).await?;
+        self.prepare(session).await
+    }
+
+    /// Restrict the benchmark operations to a slice of the keys, so that executors sharing
+    /// one dataset work on disjoint keys. The population and `verify` still address the
+    /// whole dataset
+    fn set_slice(&mut self, _slice: KeySlice) {}
+
+    /// Number of rows inserted by the population phase
+    fn population_size(&self) -> usize;
+
+    /// Insert the row with the given index, `index` is in `0..population_size()`
+    async fn populate(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        index: usize,
+    ) -> Result<()>;
+
+    /// Execute the next benchmark operation and describe it for the reporter
+    async fn next_operation(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<Operation>;
+
+    /// Recorded operations replayed by the workload. The executor then issues the n-th
+    /// operation with `replay` at its recorded offset, instead of calling `next_operation`,
+    /// and stops after the last one
+    fn trace(&self) -> Option<&Trace> {
+        None
+    }
+
+    /// Execute the n-th operation of `trace`
+    async fn replay(
+        &self,
+        _session: &GenericSession<CurrentDeserializationApi>,
+        _n: usize,
+    ) -> Result<Operation> {
+        bail!("This workload does not replay a trace")
+    }
+
+    /// Read the row with the given index back and compare it with the expected value
+    async fn verify(
+        &self,
+        _session: &GenericSession<CurrentDeserializationApi>,
+        _index: usize,
+    ) -> Result<Verification> {
+        bail!("This workload does not support verification")
+    }
+
+    /// Read the value the row with the given index has before the benchmark starts. Workloads
+    /// that verify the changes of the run rather than absolute values, e.g. counters, compare
+    /// with it after the run
+    async fn read_initial(
+        &self,
+        _session: &GenericSession<CurrentDeserializationApi>,
+        _index: usize,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// The expected values are tracked by the process that ran the benchmark, so the dataset
+    /// is verified once the benchmark is over and cannot be verified separately. Every
+    /// executor verifies the rows of its slice, the indexes of the population are expected
+    /// to follow the keys
+    fn verify_after_run(&self) -> bool {
+        false
+    }
+
+    /// Drop everything created by `create_schema`
+    async fn drop_schema(&self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()>;
+
+    /// Remove all rows, keeping the schema
+    async fn truncate_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()>;
+}
+
+/// Comma-separated weights with the given names, at least one of them above 0
+fn parse_weights(s: &str, names: &[&str]) -> Result<Vec<u32>> {
+    let weights: Vec<u32> = s
+        .split(',')
+        .map(|w| {
+            w.trim()
+                .parse::<u32>()
+                .map_err(|e| anyhow!("Invalid weight '{}' of '{}': {}", w, s, e))
+        })
+        .collect::<Result<_>>()?;
+    if weights.len() != names.len() {
+        bail!(
+            "Mix '{}' must have {} weights: <{}>",
+            s,
+            names.len(),
+            names.join(">,<")
+        );
+    }
+    if weights.iter().all(|w| *w == 0) {
+        bail!(
+            "At least one weight of the mix '{}' must be greater than 0",
+            s
+        );
+    }
+    Ok(weights)
+}
@@ -0,0 +1,277 @@
+use crate::consistency::{ConsistencyLevel, StatementConsistency};
+use crate::distribution::{KeyDistribution, KeySampler, KeySlice};
+use crate::generator::ValueGenerator;
+use crate::profile::Profile;
+use crate::reporter::{Operation, QueryType};
+use crate::workload::Workload;
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use rand::distributions::{Distribution, WeightedIndex};
+use scylla::frame::response::result::CqlValue;
+use scylla::prepared_statement::PreparedStatement;
+use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Workload built from a declarative profile file, see [`Profile`]
+pub struct ProfileWorkload {
+    profile: Profile,
+    key_distribution: KeyDistribution,
+    read_consistency: StatementConsistency,
+    write_consistency: StatementConsistency,
+    seed: u64,
+    default_rows: usize,
+    /// Rows of this executor when several executors share the dataset
+    slice: KeySlice,
+    statements: Vec<Statement>,
+    population: Option<usize>,
+    phases: Vec<Phase>,
+    started_at: OnceLock<Instant>,
+}
+
+struct Statement {
+    query_type: QueryType,
+    prepared: PreparedStatement,
+    consistency: Option<ConsistencyLevel>,
+    generators: Vec<ValueGenerator>,
+    keys: KeySampler,
+}
+
+struct Phase {
+    ends_after: Duration,
+    weights: WeightedIndex<u32>,
+}
+
+impl ProfileWorkload {
+    pub fn new(profile: Profile, key_distribution: KeyDistribution, seed: u64) -> ProfileWorkload {
+        ProfileWorkload {
+            profile,
+            key_distribution,
+            read_consistency: StatementConsistency::default(),
+            write_consistency: StatementConsistency::default(),
+            seed,
+            default_rows: 0,
+            slice: KeySlice::default(),
+            statements: Vec::new(),
+            population: None,
+            phases: Vec::new(),
+            started_at: OnceLock::new(),
+        }
+    }
+
+    /// Consistency settings of the statements that do not set their own, `SELECT`
+    /// statements use `read`, all others `write`
+    pub fn with_consistency(
+        mut self,
+        read: StatementConsistency,
+        write: StatementConsistency,
+    ) -> ProfileWorkload {
+        self.read_consistency = read;
+        self.write_consistency = write;
+        self
+    }
+
+    /// Number of rows the statements pick indexes from when the profile has no population,
+    /// e.g. the rows of an existing table benchmarked with `--query`
+    pub fn with_default_rows(mut self, rows: usize) -> ProfileWorkload {
+        self.default_rows = rows;
+        self
+    }
+
+    fn rows(&self) -> usize {
+        self.profile
+            .population
+            .as_ref()
+            .map_or(self.default_rows, |p| p.rows)
+    }
+
+    /// Weights of the phase the benchmark is in, the last phase is kept once all of them passed
+    fn current_phase(&self) -> &Phase {
+        let elapsed = self.started_at.get_or_init(Instant::now).elapsed();
+        self.phases
+            .iter()
+            .find(|phase| elapsed < phase.ends_after)
+            .unwrap_or_else(|| self.phases.last().unwrap())
+    }
+
+    async fn execute(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        statement: &Statement,
+        index: u64,
+    ) -> Result<Operation> {
+        let values: Vec<CqlValue> = statement
+            .generators
+            .iter()
+            .map(|g| g.generate(index))
+            .collect();
+        session
+            .execute_unpaged(&statement.prepared, &values)
+            .await
+            .with_context(|| format!("Statement '{}' failed", statement.query_type))?;
+        let mut operation =
+            Operation::new(statement.query_type).with_consistency(statement.consistency);
+        let blob_bytes: Option<usize> = values
+            .iter()
+            .filter_map(|v| match v {
+                CqlValue::Blob(blob) => Some(blob.len()),
+                _ => None,
+            })
+            .reduce(|a, b| a + b);
+        if let Some(size) = blob_bytes {
+            operation = operation.with_value_size(size);
+        }
+        Ok(operation)
+    }
+}
+
+#[async_trait]
+impl Workload for ProfileWorkload {
+    async fn create_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        if let Some(keyspace) = &self.profile.schema.keyspace {
+            session.query_unpaged(keyspace.as_str(), &[]).await?;
+        }
+        for ddl in &self.profile.schema.tables {
+            session.query_unpaged(ddl.as_str(), &[]).await?;
+        }
+        Ok(())
+    }
+
+    async fn prepare(&mut self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()> {
+        let mut statements = Vec::new();
+        for (name, spec) in &self.profile.statements {
+            let mut prepared = session
+                .prepare(spec.cql.as_str())
+                .await
+                .with_context(|| format!("Failed to prepare statement '{}'", name))?;
+            let defaults = if spec.cql.trim_start().to_uppercase().starts_with("SELECT") {
+                self.read_consistency
+            } else {
+                self.write_consistency
+            };
+            let consistency = StatementConsistency {
+                consistency: spec.consistency.or(defaults.consistency),
+                serial_consistency: spec.serial_consistency.or(defaults.serial_consistency),
+            };
+            consistency.apply(&mut prepared);
+            let generators = prepared
+                .get_variable_col_specs()
+                .iter()
+                .map(|col| {
+                    let spec = self
+                        .profile
+                        .generators
+                        .get(col.name())
+                        .cloned()
+                        .unwrap_or_default();
+                    ValueGenerator::new(col.name(), col.typ(), spec, self.seed)
+                })
+                .collect::<Result<Vec<_>>>()
+                .with_context(|| format!("Statement '{}'", name))?;
+            statements.push(Statement {
+                // statement names live as long as the benchmark, the report refers to them
+                query_type: QueryType::Custom(Box::leak(name.clone().into_boxed_str())),
+                prepared,
+                consistency: consistency.consistency,
+                generators,
+                keys: spec
+                    .key_distribution
+                    .as_ref()
+                    .unwrap_or(&self.key_distribution)
+                    .sampler(self.rows())
+                    .slice(self.slice),
+            });
+        }
+        let names: Vec<&String> = self.profile.statements.keys().collect();
+        let default_weights: Vec<u32> =
+            self.profile.statements.values().map(|s| s.weight).collect();
+        let mut phases = Vec::new();
+        let mut ends_after = Duration::ZERO;
+        for phase in &self.profile.phases {
+            ends_after += phase.duration;
+            let weights = match &phase.weights {
+                Some(weights) => names
+                    .iter()
+                    .map(|name| weights.get(*name).copied().unwrap_or(0))
+                    .collect(),
+                None => default_weights.clone(),
+            };
+            phases.push(Phase {
+                ends_after,
+                weights: WeightedIndex::new(weights)
+                    .with_context(|| format!("Invalid weights in phase '{}'", phase.name))?,
+            });
+        }
+        if phases.is_empty() {
+            phases.push(Phase {
+                ends_after: Duration::MAX,
+                weights: WeightedIndex::new(default_weights)?,
+            });
+        }
+        self.population = self
+            .profile
+            .population
+            .as_ref()
+            .and_then(|p| names.iter().position(|name| **name == p.statement));
+        self.statements = statements;
+        self.phases = phases;
+        Ok(())
+    }
+
+    fn set_slice(&mut self, slice: KeySlice) {
+        self.slice = slice;
+    }
+
+    fn population_size(&self) -> usize {
+        self.profile.population.as_ref().map_or(0, |p| p.rows)
+    }
+
+    async fn populate(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        index: usize,
+    ) -> Result<()> {
+        let statement = self
+            .population
+            .map(|i| &self.statements[i])
+            .ok_or_else(|| anyhow!("Schema is not set up, statements are not prepared"))?;
+        self.execute(session, statement, index as u64).await?;
+        Ok(())
+    }
+
+    async fn next_operation(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<Operation> {
+        let (statement, index) = {
+            let mut rng = rand::thread_rng();
+            let statement = &self.statements[self.current_phase().weights.sample(&mut rng)];
+            (statement, statement.keys.sample(&mut rng) as u64)
+        };
+        self.execute(session, statement, index).await
+    }
+
+    async fn drop_schema(&self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()> {
+        for statement in &self.profile.schema.drop {
+            session.query_unpaged(statement.as_str(), &[]).await?;
+        }
+        Ok(())
+    }
+
+    async fn truncate_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        if self.profile.schema.truncate.is_empty() {
+            bail!("Profile does not define schema.truncate statements");
+        }
+        for statement in &self.profile.schema.truncate {
+            session.query_unpaged(statement.as_str(), &[]).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+use crate::consistency::StatementConsistency;
+use crate::reporter::{Operation, QueryType};
+use crate::schema::SchemaConfig;
+use crate::trace::{Trace, TraceOperation};
+use crate::workload::Workload;
+use crate::write_options::{Using, WriteOptions, USING_CLAUSE};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use rand::Rng;
+use scylla::prepared_statement::PreparedStatement;
+use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Re-issues the operations of a recorded trace against the key/value table, at the
+/// recorded offsets scaled by the replay speed. The executor paces the operations, see
+/// `Workload::trace`. The trace is not sliced, a single executor replays all of it
+pub struct ReplayWorkload {
+    path: PathBuf,
+    speed: f64,
+    read_consistency: StatementConsistency,
+    write_consistency: StatementConsistency,
+    write_options: WriteOptions,
+    trace: Option<Trace>,
+    /// Index of the first entry of every distinct key, inserted by the population
+    population: Vec<usize>,
+    statements: Option<Statements>,
+    schema: SchemaConfig,
+}
+
+struct Statements {
+    read: PreparedStatement,
+    write: PreparedStatement,
+    delete: PreparedStatement,
+}
+
+/// Default name of the table, the one of the key/value workload
+const TABLE: &str = "test";
+
+impl ReplayWorkload {
+    pub fn new(path: PathBuf, speed: f64) -> ReplayWorkload {
+        ReplayWorkload {
+            path,
+            speed,
+            read_consistency: StatementConsistency::default(),
+            write_consistency: StatementConsistency::default(),
+            write_options: WriteOptions::default(),
+            trace: None,
+            population: Vec::new(),
+            statements: None,
+            schema: SchemaConfig::default(),
+        }
+    }
+
+    /// Consistency settings of the reads, and of the writes and deletes
+    pub fn with_consistency(
+        mut self,
+        read: StatementConsistency,
+        write: StatementConsistency,
+    ) -> ReplayWorkload {
+        self.read_consistency = read;
+        self.write_consistency = write;
+        self
+    }
+
+    /// Keyspace, table name and table options
+    pub fn with_schema(mut self, schema: SchemaConfig) -> ReplayWorkload {
+        self.schema = schema;
+        self
+    }
+
+    /// TTL and timestamps of the writes
+    pub fn with_write_options(mut self, write_options: WriteOptions) -> ReplayWorkload {
+        self.write_options = write_options;
+        self
+    }
+
+    fn prepared(&self) -> Result<(&Trace, &Statements)> {
+        self.trace
+            .as_ref()
+            .zip(self.statements.as_ref())
+            .ok_or_else(|| anyhow!("Schema is not set up, the trace is not loaded"))
+    }
+
+    async fn write(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        statement: &PreparedStatement,
+        key: &str,
+        value_size: usize,
+        using: Using,
+    ) -> Result<()> {
+        let mut value = vec![0u8; value_size];
+        rand::thread_rng().fill(&mut value[..]);
+        session
+            .execute_unpaged(statement, (key, value, using.ttl, using.timestamp))
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Workload for ReplayWorkload {
+    async fn create_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        let create_table = self.schema.create_table(
+            &self.schema.table(TABLE),
+            "key text PRIMARY KEY, value blob",
+            &[],
+        );
+        self.schema.create(session, &[create_table]).await
+    }
+
+    /// Load the trace along with the statements
+    async fn prepare(&mut self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()> {
+        let trace = Trace::load(&self.path, self.speed)?;
+        println!(
+            "Replaying {} operations over {:?} from {}, speed: {}",
+            trace.len(),
+            trace.duration(),
+            self.path.display(),
+            self.speed
+        );
+        let mut keys = HashSet::new();
+        self.population = trace
+            .entries()
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| keys.insert(entry.key.as_str()))
+            .map(|(i, _)| i)
+            .collect();
+        let table = self.schema.table(TABLE);
+        let (schema, read, write) = (&self.schema, self.read_consistency, self.write_consistency);
+        let read = schema
+            .prepare(
+                session,
+                format!("SELECT * FROM {table} WHERE key = ?"),
+                read,
+            )
+            .await?;
+        let delete = schema
+            .prepare(session, format!("DELETE FROM {table} WHERE key = ?"), write)
+            .await?;
+        let write = schema
+            .prepare(
+                session,
+                format!("INSERT INTO {table} (key, value) VALUES (?, ?) {USING_CLAUSE}"),
+                write,
+            )
+            .await?;
+        self.trace = Some(trace);
+        self.statements = Some(Statements {
+            read,
+            write,
+            delete,
+        });
+        Ok(())
+    }
+
+    /// Every distinct key of the trace, with the value size of its first operation
+    fn population_size(&self) -> usize {
+        self.population.len()
+    }
+
+    async fn populate(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        index: usize,
+    ) -> Result<()> {
+        let (trace, statements) = self.prepared()?;
+        let entry = trace.entry(self.population[index]);
+        self.write(
+            session,
+            &statements.write,
+            &entry.key,
+            entry.value_size,
+            self.write_options.next_population(),
+        )
+        .await
+    }
+
+    async fn next_operation(
+        &self,
+        _session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<Operation> {
+        Err(anyhow!(
+            "Trace operations are issued by their index, see Workload::replay"
+        ))
+    }
+
+    fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    async fn replay(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        n: usize,
+    ) -> Result<Operation> {
+        let (trace, statements) = self.prepared()?;
+        let entry = trace.entry(n);
+        match entry.operation {
+            TraceOperation::Read => {
+                session
+                    .execute_unpaged(&statements.read, (&entry.key,))
+                    .await
+                    .with_context(|| format!("Reading key '{}' failed", entry.key))?;
+                Ok(Operation::new(QueryType::Read)
+                    .with_value_size(entry.value_size)
+                    .with_consistency(self.read_consistency.consistency))
+            }
+            TraceOperation::Write => {
+                self.write(
+                    session,
+                    &statements.write,
+                    &entry.key,
+                    entry.value_size,
+                    self.write_options.next(),
+                )
+                .await
+                .with_context(|| format!("Writing key '{}' failed", entry.key))?;
+                Ok(Operation::new(QueryType::Write)
+                    .with_value_size(entry.value_size)
+                    .with_consistency(self.write_consistency.consistency))
+            }
+            TraceOperation::Delete => {
+                session
+                    .execute_unpaged(&statements.delete, (&entry.key,))
+                    .await
+                    .with_context(|| format!("Deleting key '{}' failed", entry.key))?;
+                Ok(Operation::new(QueryType::RowDelete)
+                    .with_consistency(self.write_consistency.consistency))
+            }
+        }
+    }
+
+    async fn drop_schema(&self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()> {
+        session
+            .query_unpaged(self.schema.drop_schema(TABLE), &[])
+            .await?;
+        Ok(())
+    }
+
+    async fn truncate_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        session
+            .query_unpaged(format!("TRUNCATE {}", self.schema.table(TABLE)), &[])
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,478 @@
+use crate::consistency::StatementConsistency;
+use crate::distribution::{KeyDistribution, KeySampler, KeySlice, ValueSizeDistribution};
+use crate::generator::index_rng;
+use crate::reporter::{Operation, QueryType};
+use crate::schema::SchemaConfig;
+use crate::workload::{parse_weights, Workload};
+use crate::write_options::{Using, WriteOptions, USING_CLAUSE};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use parse_duration::parse;
+use rand::distributions::{Alphanumeric, DistString, Distribution, WeightedIndex};
+use rand::{random, Rng};
+use scylla::frame::value::CqlTimestamp;
+use scylla::prepared_statement::PreparedStatement;
+use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Shape of the series and of the reads
+#[derive(Clone, Debug)]
+pub struct TimeSeriesConfig {
+    /// Time between two points of the history inserted by the population
+    pub interval: Duration,
+    /// Time span of a partition, the points of a sensor are split into partitions of
+    /// consecutive buckets
+    pub bucket: Duration,
+    /// Points of every sensor inserted by the population, ending at `history_end`
+    pub history: usize,
+    /// End of the history in milliseconds since the Unix epoch. It is part of the options
+    /// rather than the time the workload is created, so every executor and every later run
+    /// of the same dataset work with the same history
+    pub history_end: i64,
+    /// `LIMIT` of the reads of the newest points
+    pub latest_points: usize,
+    /// Length of the time-range reads
+    pub range: Duration,
+    pub read_mix: TimeSeriesReadMix,
+    pub window: CompactionWindow,
+}
+
+/// Relative weights of the kinds of reads, parsed from `<latest>,<range>`, e.g. `3,1`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeSeriesReadMix {
+    pub latest: u32,
+    pub range: u32,
+}
+
+/// Time window of `TimeWindowCompactionStrategy`, parsed from a duration of whole minutes,
+/// e.g. `30m`, `1h` or `1d`, and stored in the largest unit that divides it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompactionWindow {
+    pub size: u64,
+    pub unit: &'static str,
+}
+
+/// Sensors that append points in time order to partitions of `(sensor, bucket)`, clustered
+/// by timestamp, newest first. The table uses `TimeWindowCompactionStrategy`.
+///
+/// The population inserts `history` points per sensor, `interval` apart and ending at
+/// `history_end`. Writes append a point to a sensor at the current time, strictly after
+/// its previous point. Reads fetch the `latest_points` newest points of a sensor, going back
+/// to the previous bucket if the bucket of its newest point has fewer, or the points of a
+/// random `range` of time between the start of the history and its newest point, bucket by
+/// bucket.
+pub struct TimeSeriesWorkload {
+    reads_percentage: f32,
+    sensors: usize,
+    key_string_length: usize,
+    value_sizes: ValueSizeDistribution,
+    seed: u64,
+    config: TimeSeriesConfig,
+    read_kinds: WeightedIndex<u32>,
+    /// Timestamp of the newest point of every sensor, the last one of the history until this
+    /// process appends after it
+    last_points: Vec<AtomicI64>,
+    read_consistency: StatementConsistency,
+    write_consistency: StatementConsistency,
+    read_keys: KeySampler,
+    write_keys: KeySampler,
+    statements: Option<Statements>,
+    write_options: WriteOptions,
+    schema: SchemaConfig,
+}
+
+struct Statements {
+    write: PreparedStatement,
+    latest: PreparedStatement,
+    range: PreparedStatement,
+}
+
+/// Default name of the table
+const TABLE: &str = "time_series";
+
+/// Query types of the kinds of reads, in the order of their weights
+const READ_KINDS: [QueryType; 2] = [QueryType::LatestRead, QueryType::RangeRead];
+
+impl TimeSeriesWorkload {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sensors: usize,
+        key_string_length: usize,
+        value_sizes: &ValueSizeDistribution,
+        reads_percentage: f32,
+        read_distribution: &KeyDistribution,
+        write_distribution: &KeyDistribution,
+        seed: u64,
+        config: TimeSeriesConfig,
+    ) -> Result<TimeSeriesWorkload> {
+        let mix = &config.read_mix;
+        let read_kinds = WeightedIndex::new([mix.latest, mix.range])?;
+        let last_point = config.history_end - config.interval.as_millis() as i64;
+        println!(
+            "Using {sensors} sensors with {} points of history {:?} apart, time bucket: {:?}, value size: {value_sizes}, seed: {seed}",
+            config.history, config.interval, config.bucket
+        );
+        Ok(TimeSeriesWorkload {
+            reads_percentage,
+            sensors,
+            key_string_length,
+            value_sizes: value_sizes.clone(),
+            seed,
+            config,
+            read_kinds,
+            last_points: (0..sensors).map(|_| AtomicI64::new(last_point)).collect(),
+            read_consistency: StatementConsistency::default(),
+            write_consistency: StatementConsistency::default(),
+            read_keys: read_distribution.sampler(sensors),
+            write_keys: write_distribution.sampler(sensors),
+            statements: None,
+            write_options: WriteOptions::default(),
+            schema: SchemaConfig::default(),
+        })
+    }
+
+    /// Consistency settings of the reads and the write statement
+    pub fn with_consistency(
+        mut self,
+        read: StatementConsistency,
+        write: StatementConsistency,
+    ) -> TimeSeriesWorkload {
+        self.read_consistency = read;
+        self.write_consistency = write;
+        self
+    }
+
+    /// Keyspace, table name and table options
+    pub fn with_schema(mut self, schema: SchemaConfig) -> TimeSeriesWorkload {
+        self.schema = schema;
+        self
+    }
+
+    /// TTL and timestamps of the writes
+    pub fn with_write_options(mut self, write_options: WriteOptions) -> TimeSeriesWorkload {
+        self.write_options = write_options;
+        self
+    }
+
+    /// Name of the sensor with the given index
+    fn sensor(&self, sensor: usize) -> String {
+        Alphanumeric.sample_string(
+            &mut index_rng(self.seed, sensor as u64),
+            self.key_string_length,
+        )
+    }
+
+    /// Start of the bucket the timestamp falls into, in milliseconds
+    fn bucket(&self, timestamp: i64) -> i64 {
+        let bucket = self.config.bucket.as_millis() as i64;
+        timestamp - timestamp.rem_euclid(bucket)
+    }
+
+    /// Timestamp of the first point of the history
+    fn history_start(&self) -> i64 {
+        self.config.history_end
+            - (self.config.history as i64) * self.config.interval.as_millis() as i64
+    }
+
+    fn statements(&self) -> Result<&Statements> {
+        self.statements
+            .as_ref()
+            .ok_or_else(|| anyhow!("Schema is not set up, statements are not prepared"))
+    }
+
+    /// Write a point of the sensor with the given value, returns the value size
+    async fn write(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        sensor: usize,
+        timestamp: i64,
+        value: Vec<u8>,
+        using: Using,
+    ) -> Result<usize> {
+        let Using {
+            ttl,
+            timestamp: write_timestamp,
+        } = using;
+        session
+            .execute_unpaged(
+                &self.statements()?.write,
+                (
+                    self.sensor(sensor),
+                    CqlTimestamp(self.bucket(timestamp)),
+                    CqlTimestamp(timestamp),
+                    &value,
+                    ttl,
+                    write_timestamp,
+                ),
+            )
+            .await?;
+        Ok(value.len())
+    }
+
+    /// Read the newest points of the sensor, from the bucket of its newest point and the one
+    /// before it if needed, returns the number of points read
+    async fn read_latest(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        sensor: usize,
+    ) -> Result<usize> {
+        let latest = &self.statements()?.latest;
+        let bucket = self.bucket(self.last_points[sensor].load(Ordering::Relaxed));
+        let sensor = self.sensor(sensor);
+        let mut points = 0;
+        for bucket in [bucket, bucket - self.config.bucket.as_millis() as i64] {
+            let limit = (self.config.latest_points - points) as i32;
+            points += session
+                .execute_unpaged(latest, (&sensor, CqlTimestamp(bucket), limit))
+                .await?
+                .into_rows_result()?
+                .rows_num();
+            if points >= self.config.latest_points {
+                break;
+            }
+        }
+        Ok(points)
+    }
+
+    /// Read the points of the sensor within a random range of time that starts in the history
+    /// and ends by its newest point, bucket by bucket, returns the number of points read
+    async fn read_range(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        sensor: usize,
+    ) -> Result<usize> {
+        let range_statement = &self.statements()?.range;
+        let range = self.config.range.as_millis() as i64;
+        let start = {
+            let first = self.history_start();
+            // the range ends after the newest point, which it includes
+            let newest = self.last_points[sensor].load(Ordering::Relaxed);
+            let last = (newest + 1 - range).max(first);
+            rand::thread_rng().gen_range(first..=last)
+        };
+        let end = start + range;
+        let sensor = self.sensor(sensor);
+        let mut points = 0;
+        let mut bucket = self.bucket(start);
+        while bucket < end {
+            points += session
+                .execute_unpaged(
+                    range_statement,
+                    (
+                        &sensor,
+                        CqlTimestamp(bucket),
+                        CqlTimestamp(start),
+                        CqlTimestamp(end),
+                    ),
+                )
+                .await?
+                .into_rows_result()?
+                .rows_num();
+            bucket += self.config.bucket.as_millis() as i64;
+        }
+        Ok(points)
+    }
+}
+
+#[async_trait]
+impl Workload for TimeSeriesWorkload {
+    async fn create_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        let table = self.schema.table(TABLE);
+        let mut options = vec!["CLUSTERING ORDER BY (ts DESC)".to_string()];
+        // a configured compaction replaces the time window one
+        if self.schema.compaction.is_none() {
+            options.push(format!(
+                "compaction = {{ 'class' : 'TimeWindowCompactionStrategy', 'compaction_window_unit' : '{}', 'compaction_window_size' : {} }}",
+                self.config.window.unit, self.config.window.size
+            ));
+        }
+        let create_table = self.schema.create_table(
+            &table,
+            "sensor text, bucket timestamp, ts timestamp, value blob, PRIMARY KEY ((sensor, bucket), ts)",
+            &options,
+        );
+        self.schema.create(session, &[create_table]).await
+    }
+
+    async fn prepare(&mut self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()> {
+        let table = self.schema.table(TABLE);
+        let schema = &self.schema;
+        let prepare = |cql, consistency| schema.prepare(session, cql, consistency);
+        self.statements = Some(Statements {
+            write: prepare(
+                format!("INSERT INTO {table} (sensor, bucket, ts, value) VALUES (?, ?, ?, ?) {USING_CLAUSE}"),
+                self.write_consistency,
+            )
+            .await?,
+            latest: prepare(
+                format!("SELECT ts, value FROM {table} WHERE sensor = ? AND bucket = ? LIMIT ?"),
+                self.read_consistency,
+            )
+            .await?,
+            range: prepare(
+                format!("SELECT ts, value FROM {table} WHERE sensor = ? AND bucket = ? AND ts >= ? AND ts < ?"),
+                self.read_consistency,
+            )
+            .await?,
+        });
+        Ok(())
+    }
+
+    /// Executors get disjoint sensors
+    fn set_slice(&mut self, slice: KeySlice) {
+        self.read_keys = self.read_keys.slice(slice);
+        self.write_keys = self.write_keys.slice(slice);
+    }
+
+    fn population_size(&self) -> usize {
+        self.sensors * self.config.history
+    }
+
+    /// Point `index / sensors` of sensor `index % sensors`, so the history is inserted in
+    /// time order across all sensors
+    async fn populate(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        index: usize,
+    ) -> Result<()> {
+        let (sensor, point) = (index % self.sensors, index / self.sensors);
+        let timestamp =
+            self.history_start() + (point as i64) * self.config.interval.as_millis() as i64;
+        let rng = &mut index_rng(self.seed, (self.sensors + index) as u64);
+        let mut value = vec![0u8; self.value_sizes.sample(rng)];
+        rng.fill(&mut value[..]);
+        self.write(
+            session,
+            sensor,
+            timestamp,
+            value,
+            self.write_options.next_population(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn next_operation(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<Operation> {
+        if random::<f32>() >= self.reads_percentage {
+            let (sensor, value) = {
+                let mut rng = rand::thread_rng();
+                let sensor = self.write_keys.sample(&mut rng);
+                let mut value = vec![0u8; self.value_sizes.sample(&mut rng)];
+                rng.fill(&mut value[..]);
+                (sensor, value)
+            };
+            // points of a sensor are appended in time order, even if two writes of the
+            // sensor happen in the same millisecond
+            let now = now_millis();
+            let previous = self.last_points[sensor]
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+                    Some(now.max(last + 1))
+                })
+                .unwrap();
+            let value_size = self
+                .write(
+                    session,
+                    sensor,
+                    now.max(previous + 1),
+                    value,
+                    self.write_options.next(),
+                )
+                .await?;
+            return Ok(Operation::new(QueryType::Write)
+                .with_value_size(value_size)
+                .with_consistency(self.write_consistency.consistency));
+        }
+        let (query_type, sensor) = {
+            let mut rng = rand::thread_rng();
+            let query_type = READ_KINDS[self.read_kinds.sample(&mut rng)];
+            (query_type, self.read_keys.sample(&mut rng))
+        };
+        let rows = match query_type {
+            QueryType::LatestRead => self.read_latest(session, sensor).await?,
+            _ => self.read_range(session, sensor).await?,
+        };
+        Ok(Operation::new(query_type)
+            .with_rows(rows)
+            .with_consistency(self.read_consistency.consistency))
+    }
+
+    async fn drop_schema(&self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()> {
+        session
+            .query_unpaged(self.schema.drop_schema(TABLE), &[])
+            .await?;
+        Ok(())
+    }
+
+    async fn truncate_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        session
+            .query_unpaged(format!("TRUNCATE {}", self.schema.table(TABLE)), &[])
+            .await?;
+        Ok(())
+    }
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+impl FromStr for TimeSeriesReadMix {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let weights = parse_weights(s, &["latest", "range"])?;
+        let [latest, range] = weights[..] else {
+            unreachable!()
+        };
+        Ok(TimeSeriesReadMix { latest, range })
+    }
+}
+
+impl fmt::Display for TimeSeriesReadMix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "latest: {}, range: {}", self.latest, self.range)
+    }
+}
+
+impl FromStr for CompactionWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let window = parse(s)?;
+        if window.subsec_nanos() != 0 || window.as_secs() % 60 != 0 || window.as_secs() == 0 {
+            bail!(
+                "Compaction window '{}' must be a positive number of whole minutes",
+                s
+            );
+        }
+        let minutes = window.as_secs() / 60;
+        let (size, unit) = if minutes % (24 * 60) == 0 {
+            (minutes / (24 * 60), "DAYS")
+        } else if minutes % 60 == 0 {
+            (minutes / 60, "HOURS")
+        } else {
+            (minutes, "MINUTES")
+        };
+        Ok(CompactionWindow { size, unit })
+    }
+}
+
+impl fmt::Display for CompactionWindow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.size, self.unit)
+    }
+}
@@ -0,0 +1,521 @@
+use crate::consistency::StatementConsistency;
+use crate::distribution::{KeyDistribution, KeySampler, KeySlice, ValueSizeDistribution};
+use crate::generator::index_rng;
+use crate::reporter::{Operation, QueryType};
+use crate::schema::SchemaConfig;
+use crate::workload::{parse_weights, TombstoneAlert, Workload};
+use crate::write_options::{Using, WriteOptions, USING_CLAUSE};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use rand::distributions::{Alphanumeric, DistString, Distribution, WeightedIndex};
+use rand::{random, Rng};
+use scylla::prepared_statement::PreparedStatement;
+use scylla::serialize::row::SerializeRow;
+use scylla::statement::{PagingState, PagingStateResponse};
+use scylla::transport::errors::{DbError, QueryError};
+use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Shape of the partitions and of the multi-row reads
+#[derive(Clone, Debug)]
+pub struct WideConfig {
+    /// Number of clustering rows in every partition
+    pub rows_per_partition: usize,
+    /// `LIMIT` of slice and reverse-order reads, also the number of rows of range deletes
+    pub slice_limit: usize,
+    /// Page size of full-partition reads
+    pub page_size: usize,
+    pub read_mix: WideReadMix,
+    /// Fraction of operations that are deletes, taken out of the writes
+    pub deletes_percentage: f32,
+    pub delete_mix: WideDeleteMix,
+}
+
+/// Relative weights of the kinds of reads, parsed from `<single>,<slice>,<reverse>,<full>`,
+/// e.g. `4,3,2,1`
+#[derive(Clone, Debug, PartialEq)]
+pub struct WideReadMix {
+    pub single: u32,
+    pub slice: u32,
+    pub reverse: u32,
+    pub full: u32,
+}
+
+/// Relative weights of the kinds of deletes, parsed from `<row>,<range>,<cell>`, e.g. `1,1,0`
+#[derive(Clone, Debug, PartialEq)]
+pub struct WideDeleteMix {
+    pub row: u32,
+    pub range: u32,
+    pub cell: u32,
+}
+
+/// Partitions with a text key and `rows_per_partition` clustering rows with a bigint
+/// clustering key and a blob value. The row with a given index is regenerated from the seed
+/// whenever it is needed: row `i` is clustering row `i % rows_per_partition` of partition
+/// `i / rows_per_partition`. Key distributions pick partitions, rows within a partition
+/// are picked uniformly.
+///
+/// Writes overwrite single rows, reads are single-row reads, slices of `slice_limit` rows
+/// in clustering order or in reverse order, and full-partition reads paged by `page_size`.
+/// Deletes remove single rows, ranges of `slice_limit` rows or the value cell of a row. The
+/// workload counts the tombstones it wrote to every partition and tags reads with the count,
+/// so the report shows how read latency grows as tombstones pile up.
+pub struct WideWorkload {
+    reads_percentage: f32,
+    partitions: usize,
+    key_string_length: usize,
+    value_sizes: ValueSizeDistribution,
+    seed: u64,
+    config: WideConfig,
+    read_kinds: WeightedIndex<u32>,
+    delete_kinds: Option<WeightedIndex<u32>>,
+    /// Tombstones written to every partition by this process, each delete adds one
+    tombstones: Vec<AtomicUsize>,
+    read_consistency: StatementConsistency,
+    write_consistency: StatementConsistency,
+    read_keys: KeySampler,
+    write_keys: KeySampler,
+    statements: Option<Statements>,
+    write_options: WriteOptions,
+    schema: SchemaConfig,
+}
+
+struct Statements {
+    write: PreparedStatement,
+    single: PreparedStatement,
+    slice: PreparedStatement,
+    reverse: PreparedStatement,
+    full: PreparedStatement,
+    delete_row: PreparedStatement,
+    delete_range: PreparedStatement,
+    delete_cell: PreparedStatement,
+}
+
+/// Default name of the table
+const TABLE: &str = "wide";
+
+/// Query types of the kinds of reads, in the order of their weights
+const READ_KINDS: [QueryType; 4] = [
+    QueryType::Read,
+    QueryType::SliceRead,
+    QueryType::ReverseRead,
+    QueryType::PartitionRead,
+];
+
+/// Query types of the kinds of deletes, in the order of their weights
+const DELETE_KINDS: [QueryType; 3] = [
+    QueryType::RowDelete,
+    QueryType::RangeDelete,
+    QueryType::CellDelete,
+];
+
+impl WideWorkload {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        partitions: usize,
+        key_string_length: usize,
+        value_sizes: &ValueSizeDistribution,
+        reads_percentage: f32,
+        read_distribution: &KeyDistribution,
+        write_distribution: &KeyDistribution,
+        seed: u64,
+        config: WideConfig,
+    ) -> Result<WideWorkload> {
+        let mix = &config.read_mix;
+        let read_kinds = WeightedIndex::new([mix.single, mix.slice, mix.reverse, mix.full])?;
+        let mix = &config.delete_mix;
+        let delete_kinds = (config.deletes_percentage > 0.0)
+            .then(|| WeightedIndex::new([mix.row, mix.range, mix.cell]))
+            .transpose()?;
+        println!(
+            "Using {partitions} partitions of {} rows, key length: {key_string_length}, value size: {value_sizes}, seed: {seed}",
+            config.rows_per_partition
+        );
+        Ok(WideWorkload {
+            reads_percentage,
+            partitions,
+            key_string_length,
+            value_sizes: value_sizes.clone(),
+            seed,
+            config,
+            read_kinds,
+            delete_kinds,
+            tombstones: (0..partitions).map(|_| AtomicUsize::new(0)).collect(),
+            read_consistency: StatementConsistency::default(),
+            write_consistency: StatementConsistency::default(),
+            read_keys: read_distribution.sampler(partitions),
+            write_keys: write_distribution.sampler(partitions),
+            statements: None,
+            write_options: WriteOptions::default(),
+            schema: SchemaConfig::default(),
+        })
+    }
+
+    /// Consistency settings of the reads and the write statement
+    pub fn with_consistency(
+        mut self,
+        read: StatementConsistency,
+        write: StatementConsistency,
+    ) -> WideWorkload {
+        self.read_consistency = read;
+        self.write_consistency = write;
+        self
+    }
+
+    /// Keyspace, table name and table options
+    pub fn with_schema(mut self, schema: SchemaConfig) -> WideWorkload {
+        self.schema = schema;
+        self
+    }
+
+    /// TTL and timestamps of the writes
+    pub fn with_write_options(mut self, write_options: WriteOptions) -> WideWorkload {
+        self.write_options = write_options;
+        self
+    }
+
+    /// Key of the partition with the given index
+    fn key(&self, partition: usize) -> String {
+        Alphanumeric.sample_string(
+            &mut index_rng(self.seed, partition as u64),
+            self.key_string_length,
+        )
+    }
+
+    /// Value of the row with the given index. Rows are numbered after the partitions, so
+    /// their generators do not overlap with the ones of the partition keys
+    fn value(&self, row: usize) -> Vec<u8> {
+        let rng = &mut index_rng(self.seed, (self.partitions + row) as u64);
+        let mut value = vec![0u8; self.value_sizes.sample(rng)];
+        rng.fill(&mut value[..]);
+        value
+    }
+
+    fn statements(&self) -> Result<&Statements> {
+        self.statements
+            .as_ref()
+            .ok_or_else(|| anyhow!("Schema is not set up, statements are not prepared"))
+    }
+
+    /// Write the clustering row `ck` of the given partition, returns the value size
+    async fn write(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        partition: usize,
+        ck: usize,
+        using: Using,
+    ) -> Result<usize> {
+        let value = self.value(partition * self.config.rows_per_partition + ck);
+        let Using { ttl, timestamp } = using;
+        session
+            .execute_unpaged(
+                &self.statements()?.write,
+                (self.key(partition), ck as i64, &value, ttl, timestamp),
+            )
+            .await?;
+        Ok(value.len())
+    }
+
+    /// Execute a read and return the number of rows it returned and its tombstone alert
+    async fn read_rows(
+        session: &GenericSession<CurrentDeserializationApi>,
+        statement: &PreparedStatement,
+        values: impl SerializeRow,
+    ) -> Result<(usize, Option<TombstoneAlert>)> {
+        let result = match session.execute_unpaged(statement, values).await {
+            Ok(result) => result,
+            Err(e) if is_read_failure(&e) => return Ok((0, Some(TombstoneAlert::Failure))),
+            Err(e) => return Err(e.into()),
+        };
+        let alert = tombstone_warning(result.warnings());
+        Ok((result.into_rows_result()?.rows_num(), alert))
+    }
+
+    /// Read the whole partition page by page and return the number of rows and the
+    /// tombstone alert of any of the pages
+    async fn read_partition(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        key: &str,
+    ) -> Result<(usize, Option<TombstoneAlert>)> {
+        let full = &self.statements()?.full;
+        let mut rows = 0;
+        let mut alert = None;
+        let mut paging_state = PagingState::start();
+        loop {
+            let (result, response) = match session
+                .execute_single_page(full, (key,), paging_state)
+                .await
+            {
+                Ok(page) => page,
+                Err(e) if is_read_failure(&e) => return Ok((rows, Some(TombstoneAlert::Failure))),
+                Err(e) => return Err(e.into()),
+            };
+            alert = alert.or(tombstone_warning(result.warnings()));
+            rows += result.into_rows_result()?.rows_num();
+            match response {
+                PagingStateResponse::HasMorePages { state } => paging_state = state,
+                PagingStateResponse::NoMorePages => return Ok((rows, alert)),
+            }
+        }
+    }
+
+    async fn delete(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        kinds: &WeightedIndex<u32>,
+    ) -> Result<Operation> {
+        let statements = self.statements()?;
+        let (query_type, partition, ck) = {
+            let mut rng = rand::thread_rng();
+            let query_type = DELETE_KINDS[kinds.sample(&mut rng)];
+            let partition = self.write_keys.sample(&mut rng);
+            let ck = rng.gen_range(0..self.config.rows_per_partition) as i64;
+            (query_type, partition, ck)
+        };
+        let key = self.key(partition);
+        match query_type {
+            QueryType::RowDelete => {
+                session
+                    .execute_unpaged(&statements.delete_row, (&key, ck))
+                    .await?
+            }
+            QueryType::RangeDelete => {
+                let end = ck + self.config.slice_limit as i64;
+                session
+                    .execute_unpaged(&statements.delete_range, (&key, ck, end))
+                    .await?
+            }
+            _ => {
+                session
+                    .execute_unpaged(&statements.delete_cell, (&key, ck))
+                    .await?
+            }
+        };
+        self.tombstones[partition].fetch_add(1, Ordering::Relaxed);
+        Ok(Operation::new(query_type).with_consistency(self.write_consistency.consistency))
+    }
+}
+
+#[async_trait]
+impl Workload for WideWorkload {
+    async fn create_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        let table = self.schema.table(TABLE);
+        let create_table = self.schema.create_table(
+            &table,
+            "key text, ck bigint, value blob, PRIMARY KEY (key, ck)",
+            &[],
+        );
+        self.schema.create(session, &[create_table]).await
+    }
+
+    async fn prepare(&mut self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()> {
+        let table = self.schema.table(TABLE);
+        let schema = &self.schema;
+        let prepare = |cql, consistency| schema.prepare(session, cql, consistency);
+        let (read, write) = (self.read_consistency, self.write_consistency);
+        let mut full =
+            prepare(format!("SELECT ck, value FROM {table} WHERE key = ?"), read).await?;
+        full.set_page_size(self.config.page_size as i32);
+        self.statements = Some(Statements {
+            write: prepare(
+                format!("INSERT INTO {table} (key, ck, value) VALUES (?, ?, ?) {USING_CLAUSE}"),
+                write,
+            )
+            .await?,
+            single: prepare(
+                format!("SELECT ck, value FROM {table} WHERE key = ? AND ck = ?"),
+                read,
+            )
+            .await?,
+            slice: prepare(
+                format!("SELECT ck, value FROM {table} WHERE key = ? AND ck >= ? LIMIT ?"),
+                read,
+            )
+            .await?,
+            reverse: prepare(
+                format!("SELECT ck, value FROM {table} WHERE key = ? AND ck <= ? ORDER BY ck DESC LIMIT ?"),
+                read,
+            )
+            .await?,
+            full,
+            delete_row: prepare(
+                format!("DELETE FROM {table} WHERE key = ? AND ck = ?"),
+                write,
+            )
+            .await?,
+            delete_range: prepare(
+                format!("DELETE FROM {table} WHERE key = ? AND ck >= ? AND ck < ?"),
+                write,
+            )
+            .await?,
+            delete_cell: prepare(
+                format!("DELETE value FROM {table} WHERE key = ? AND ck = ?"),
+                write,
+            )
+            .await?,
+        });
+        Ok(())
+    }
+
+    /// Executors get disjoint partitions
+    fn set_slice(&mut self, slice: KeySlice) {
+        self.read_keys = self.read_keys.slice(slice);
+        self.write_keys = self.write_keys.slice(slice);
+    }
+
+    fn population_size(&self) -> usize {
+        self.partitions * self.config.rows_per_partition
+    }
+
+    async fn populate(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+        index: usize,
+    ) -> Result<()> {
+        let rows = self.config.rows_per_partition;
+        self.write(
+            session,
+            index / rows,
+            index % rows,
+            self.write_options.next_population(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn next_operation(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<Operation> {
+        let statements = self.statements()?;
+        let operation_kind = random::<f32>();
+        if operation_kind >= self.reads_percentage {
+            if let Some(kinds) = &self.delete_kinds {
+                if operation_kind < self.reads_percentage + self.config.deletes_percentage {
+                    return self.delete(session, kinds).await;
+                }
+            }
+            let (partition, ck) = {
+                let mut rng = rand::thread_rng();
+                let partition = self.write_keys.sample(&mut rng);
+                (partition, rng.gen_range(0..self.config.rows_per_partition))
+            };
+            let value_size = self
+                .write(session, partition, ck, self.write_options.next())
+                .await?;
+            return Ok(Operation::new(QueryType::Write)
+                .with_value_size(value_size)
+                .with_consistency(self.write_consistency.consistency));
+        }
+        let (query_type, partition, ck) = {
+            let mut rng = rand::thread_rng();
+            let query_type = READ_KINDS[self.read_kinds.sample(&mut rng)];
+            let partition = self.read_keys.sample(&mut rng);
+            let ck = rng.gen_range(0..self.config.rows_per_partition) as i64;
+            (query_type, partition, ck)
+        };
+        let key = self.key(partition);
+        let limit = self.config.slice_limit as i32;
+        let operation = Operation::new(query_type)
+            .with_consistency(self.read_consistency.consistency)
+            .with_tombstones(self.tombstones[partition].load(Ordering::Relaxed));
+        let (rows, alert) = match query_type {
+            QueryType::Read => {
+                let (_, alert) = Self::read_rows(session, &statements.single, (&key, ck)).await?;
+                return Ok(operation.with_tombstone_alert(alert));
+            }
+            QueryType::SliceRead => {
+                Self::read_rows(session, &statements.slice, (&key, ck, limit)).await?
+            }
+            QueryType::ReverseRead => {
+                Self::read_rows(session, &statements.reverse, (&key, ck, limit)).await?
+            }
+            _ => self.read_partition(session, &key).await?,
+        };
+        Ok(operation.with_rows(rows).with_tombstone_alert(alert))
+    }
+
+    async fn drop_schema(&self, session: &GenericSession<CurrentDeserializationApi>) -> Result<()> {
+        session
+            .query_unpaged(self.schema.drop_schema(TABLE), &[])
+            .await?;
+        Ok(())
+    }
+
+    async fn truncate_schema(
+        &self,
+        session: &GenericSession<CurrentDeserializationApi>,
+    ) -> Result<()> {
+        session
+            .query_unpaged(format!("TRUNCATE {}", self.schema.table(TABLE)), &[])
+            .await?;
+        Ok(())
+    }
+}
+
+/// Responses of reads that touched many tombstones carry a warning about it
+fn tombstone_warning<'a>(mut warnings: impl Iterator<Item = &'a str>) -> Option<TombstoneAlert> {
+    warnings
+        .any(|warning| warning.to_lowercase().contains("tombstone"))
+        .then_some(TombstoneAlert::Warning)
+}
+
+/// Replicas fail reads that go over the tombstone failure threshold
+fn is_read_failure(error: &QueryError) -> bool {
+    matches!(error, QueryError::DbError(DbError::ReadFailure { .. }, _))
+}
+
+impl FromStr for WideReadMix {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let weights = parse_weights(s, &["single", "slice", "reverse", "full"])?;
+        let [single, slice, reverse, full] = weights[..] else {
+            unreachable!()
+        };
+        Ok(WideReadMix {
+            single,
+            slice,
+            reverse,
+            full,
+        })
+    }
+}
+
+impl fmt::Display for WideReadMix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "single: {}, slice: {}, reverse: {}, full: {}",
+            self.single, self.slice, self.reverse, self.full
+        )
+    }
+}
+
+impl FromStr for WideDeleteMix {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let weights = parse_weights(s, &["row", "range", "cell"])?;
+        let [row, range, cell] = weights[..] else {
+            unreachable!()
+        };
+        Ok(WideDeleteMix { row, range, cell })
+    }
+}
+
+impl fmt::Display for WideDeleteMix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "row: {}, range: {}, cell: {}",
+            self.row, self.range, self.cell
+        )
+    }
+}
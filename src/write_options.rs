@@ -0,0 +1,217 @@
+use crate::distribution::ValueSizeDistribution;
+use anyhow::{bail, Result};
+use parse_duration::parse;
+use rand::Rng;
+use scylla::frame::value::MaybeUnset;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Where the write timestamps come from.
+///
+/// Parsed from:
+/// - `server` - the coordinator assigns the timestamp
+/// - `monotonic` - the client sends the current time in microseconds, strictly increasing
+///   even if the clock goes back or two writes happen in the same microsecond
+/// - `skewed:<max skew>` - the client sends the current time moved by a random offset of
+///   up to the max skew in either direction, e.g. `skewed:500ms`, so concurrent writes
+///   of a key race with last-write-wins conflict resolution
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TimestampMode {
+    #[default]
+    Server,
+    Monotonic,
+    Skewed(Duration),
+}
+
+/// Largest TTL the server accepts, 20 years in seconds
+pub const MAX_TTL: usize = 630_720_000;
+
+/// TTL of the writes in seconds, drawn from a distribution. Parsed from the same forms as
+/// `ValueSizeDistribution`, but every TTL is between 1 and `MAX_TTL`: fixed, uniform and
+/// histogram TTLs and normal and log-normal maxes outside of it are rejected, normal and
+/// log-normal samples are clamped to it, and their max is `MAX_TTL` if not given
+#[derive(Clone, Debug)]
+pub struct TtlDistribution(ValueSizeDistribution);
+
+/// `USING TTL` and `USING TIMESTAMP` settings of the writes of the built-in workloads
+#[derive(Debug, Default)]
+pub struct WriteOptions {
+    /// TTL of the benchmark writes, the population never expires
+    pub ttl: Option<TtlDistribution>,
+    pub timestamps: TimestampMode,
+    /// Last timestamp of the monotonic mode
+    last_timestamp: AtomicI64,
+}
+
+/// `USING` clause of the writes, with a bind marker for the TTL and one for the timestamp.
+/// Unset markers keep the server defaults: the default TTL of the table and the time of
+/// the coordinator
+pub const USING_CLAUSE: &str = "USING TTL ? AND TIMESTAMP ?";
+
+/// Values of the `USING` bind markers of a write
+pub struct Using {
+    pub ttl: MaybeUnset<i32>,
+    pub timestamp: MaybeUnset<i64>,
+}
+
+impl WriteOptions {
+    pub fn new(ttl: Option<TtlDistribution>, timestamps: TimestampMode) -> WriteOptions {
+        WriteOptions {
+            ttl,
+            timestamps,
+            last_timestamp: AtomicI64::new(0),
+        }
+    }
+
+    /// Values of the bind markers of `USING_CLAUSE` for the next write
+    pub fn next(&self) -> Using {
+        let mut rng = rand::thread_rng();
+        let ttl = match &self.ttl {
+            Some(ttl) => MaybeUnset::Set(ttl.sample(&mut rng) as i32),
+            None => MaybeUnset::Unset,
+        };
+        let timestamp = match self.timestamps {
+            TimestampMode::Server => MaybeUnset::Unset,
+            TimestampMode::Monotonic => {
+                let now = now_micros();
+                let previous = self
+                    .last_timestamp
+                    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+                        Some(now.max(last + 1))
+                    })
+                    .unwrap();
+                MaybeUnset::Set(now.max(previous + 1))
+            }
+            TimestampMode::Skewed(max_skew) => {
+                let max_skew = max_skew.as_micros() as i64;
+                MaybeUnset::Set(now_micros() + rng.gen_range(-max_skew..=max_skew))
+            }
+        };
+        Using { ttl, timestamp }
+    }
+
+    /// Values of the bind markers for the next write of the population, which has the
+    /// timestamps of the benchmark writes but not their TTL
+    pub fn next_population(&self) -> Using {
+        Using {
+            ttl: MaybeUnset::Unset,
+            ..self.next()
+        }
+    }
+}
+
+impl TtlDistribution {
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        self.0.sample(rng).clamp(1, MAX_TTL)
+    }
+}
+
+fn now_micros() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_micros() as i64
+}
+
+impl FromStr for TtlDistribution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let distribution = ValueSizeDistribution::parse_with_max(s, MAX_TTL)?;
+        let in_bounds = |ttl: &usize| (1..=MAX_TTL).contains(ttl);
+        let valid = match &distribution {
+            ValueSizeDistribution::Fixed(ttl) => in_bounds(ttl),
+            ValueSizeDistribution::Uniform { min, max } => in_bounds(min) && in_bounds(max),
+            ValueSizeDistribution::Histogram { sizes, .. } => sizes.iter().all(in_bounds),
+            ValueSizeDistribution::Normal { max, .. }
+            | ValueSizeDistribution::LogNormal { max, .. } => in_bounds(max),
+        };
+        if !valid {
+            bail!("TTLs of '{}' must be between 1 and {} seconds", s, MAX_TTL);
+        }
+        Ok(TtlDistribution(distribution))
+    }
+}
+
+impl fmt::Display for TtlDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for TimestampMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, param) = s.split_once(':').unwrap_or((s, ""));
+        match (name, param) {
+            ("server", "") => Ok(TimestampMode::Server),
+            ("monotonic", "") => Ok(TimestampMode::Monotonic),
+            ("skewed", max_skew) if !max_skew.is_empty() => {
+                Ok(TimestampMode::Skewed(parse(max_skew)?))
+            }
+            _ => bail!(
+                "Unknown timestamp mode '{}', available: server, monotonic, skewed:<max skew>",
+                s
+            ),
+        }
+    }
+}
+
+impl fmt::Display for TimestampMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimestampMode::Server => write!(f, "server"),
+            TimestampMode::Monotonic => write!(f, "monotonic"),
+            TimestampMode::Skewed(max_skew) => write!(f, "skewed:{:?}", max_skew),
+        }
+    }
+}
+
+impl fmt::Display for WriteOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.ttl {
+            Some(ttl) => write!(f, "ttl_seconds: {}", ttl)?,
+            None => write!(f, "ttl_seconds: none")?,
+        }
+        write!(f, ", timestamps: {}", self.timestamps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ttl_distributions() {
+        let parse = |s: &str| s.parse::<TtlDistribution>().unwrap().to_string();
+        assert_eq!(parse("fixed:1"), "fixed:1");
+        assert_eq!(parse("fixed:630720000"), "fixed:630720000");
+        assert_eq!(parse("uniform:60,3600"), "uniform:60,3600");
+        assert_eq!(parse("normal:600,60"), "normal:600,60,630720000");
+        assert_eq!(parse("lognormal:600,1"), "lognormal:600,1,630720000");
+        assert_eq!(parse("normal:600,60,3600"), "normal:600,60,3600");
+    }
+
+    #[test]
+    fn rejects_ttls_out_of_bounds() {
+        for s in [
+            "fixed:0",
+            "fixed:630720001",
+            "uniform:0,60",
+            "uniform:60,630720001",
+            "normal:600,60,630720001",
+        ] {
+            assert!(s.parse::<TtlDistribution>().is_err(), "{} was accepted", s);
+        }
+    }
+
+    #[test]
+    fn clamps_sampled_ttls() {
+        let rng = &mut rand::thread_rng();
+        let ttl: TtlDistribution = "normal:0,1000".parse().unwrap();
+        assert!((0..1000).all(|_| (1..=MAX_TTL).contains(&ttl.sample(rng))));
+    }
+}